@@ -1,59 +1,91 @@
 use std::{env, error, fs};
 
 #[path = "src/engines.rs"]
-mod shared;
+mod engines;
 
-use compact_str::CompactString;
-use shared::{InternalSearchEngine, SearchEngineDatabase};
+#[path = "src/bangs.rs"]
+mod bangs;
+
+#[path = "src/lint.rs"]
+mod lint;
+
+use bangs::{BuildReport, Format, HttpsUpgrade, Source, SourceReport};
+use engines::{InternalSearchEngine, SearchEngineDatabase};
+use lint::{Linter, Severity};
 use time::UtcDateTime;
 
 const BANG_PATH: &str = "res/bang.json";
 
-#[derive(serde::Deserialize)]
-struct ParsedEngine {
-    #[serde(rename = "u")]
-    url: CompactString,
-    #[serde(rename = "s")]
-    name: CompactString,
-    #[serde(rename = "t")]
-    shortcut: CompactString,
-    #[serde(rename = "c")]
-    category: Option<CompactString>,
-    #[serde(rename = "sc")]
-    subcategory: Option<CompactString>,
-}
-
 fn main() -> Result<(), Box<dyn error::Error>> {
-    let parsed: Vec<ParsedEngine> = serde_json::from_slice(&fs::read(BANG_PATH)?)?;
+    println!("cargo:rerun-if-env-changed={}", bangs::BUILD_SOURCES_VAR);
+    println!("cargo:rerun-if-env-changed={}", lint::FAIL_ON_VAR);
+
+    let fail_on = match env::var(lint::FAIL_ON_VAR).as_deref() {
+        Ok("never") => None,
+        Ok(severity) => Some(severity.parse::<Severity>()?),
+        Err(_) => Some(Severity::Error),
+    };
+
+    println!("cargo:rerun-if-env-changed={}", bangs::HTTPS_UPGRADE_VAR);
+    println!("cargo:rerun-if-env-changed={}", bangs::HTTPS_EXCEPTIONS_VAR);
 
-    println!("cargo:rerun-if-changed={BANG_PATH}");
+    let upgrade =
+        env::var(bangs::HTTPS_UPGRADE_VAR).is_ok_and(|v| matches!(v.as_str(), "1" | "true"));
+    let mut https = upgrade.then(|| {
+        HttpsUpgrade::new(
+            env::var(bangs::HTTPS_EXCEPTIONS_VAR)
+                .map(|list| bangs::parse_shortcuts(&list))
+                .unwrap_or_default(),
+        )
+    });
+
+    let sources = match env::var(bangs::BUILD_SOURCES_VAR) {
+        Ok(list) => bangs::parse_sources(&list)?,
+        Err(_) => vec![Source::File {
+            format: Format::DuckDuckGo,
+            path: BANG_PATH.into(),
+        }],
+    };
 
     let mut db = SearchEngineDatabase::default();
+    let mut last_modified = None;
+    let mut report = BuildReport::default();
+    let mut upgraded = Vec::new();
+    let mut linter = Linter::default();
+
+    // later sources override the shortcuts of earlier ones
+    for source in sources {
+        let Source::File { format, path } = &source else {
+            return Err(format!("'{}' can only be used at runtime", bangs::BUILTIN_SOURCE).into());
+        };
+
+        println!("cargo:rerun-if-changed={}", path.display());
 
-    for parse in parsed {
-        let url = parse
-            .url
-            .replace("&ie={inputEncoding}", "")
-            .replace("{", "{{")
-            .replace("}", "}}")
-            // ridiculous, I know
-            .replace("{{{{{{s}}}}}}", "{s}")
-            .replace("\"", "\\\"");
-
-        // shortcuts to the duckduckgo website itself
-        if url.starts_with("/") {
-            continue;
+        let modified = fs::metadata(path)?.modified()?;
+        last_modified = last_modified.max(Some(modified));
+
+        let mut read = bangs::read(*format, path)?;
+        report.sources.push(SourceReport::new(&source, &read));
+
+        if let Some(https) = &mut https {
+            upgraded.extend(https.apply(&mut read.bangs));
         }
 
-        db.insert(
-            &parse.shortcut,
-            InternalSearchEngine {
-                name: parse.name,
-                url: url.into(),
-                category: parse.category,
-                subcategory: parse.subcategory,
-            },
-        );
+        let name = source.to_string();
+        for bang in read.bangs {
+            linter.check(&name, &bang);
+
+            db.insert_template(
+                &bang.shortcut.into(),
+                InternalSearchEngine {
+                    name: bang.name.into(),
+                    url: bang.url.into(),
+                    category: bang.category.map(Into::into),
+                    subcategory: bang.subcategory.map(Into::into),
+                },
+                bang.template,
+            );
+        }
     }
 
     assert!(
@@ -61,7 +93,7 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         "No search engines found in bang.json"
     );
     assert!(
-        db.get_engine(&shared::default::engine()).is_some(),
+        db.get_engine(&engines::default::engine()).is_some(),
         "Default engine not found in bang.json"
     );
 
@@ -69,9 +101,49 @@ fn main() -> Result<(), Box<dyn error::Error>> {
 
     let db_path = format!("{out_dir}/generated.bin");
     println!("cargo::rustc-env=LSS_DATABASE={db_path}");
-    fs::write(db_path, rkyv::to_bytes::<rkyv::rancor::Error>(&db)?)?;
+    fs::write(db_path, db.to_bytes()?)?;
+
+    let lint_path = format!("{out_dir}/lint_report.txt");
+    fs::write(
+        &lint_path,
+        linter
+            .lints
+            .iter()
+            .map(|lint| format!("{lint}\n"))
+            .collect::<String>(),
+    )?;
+
+    if let Some(severity) = fail_on {
+        let failed: Vec<_> = linter.at_least(severity).collect();
+
+        if !failed.is_empty() {
+            for lint in failed.iter().take(20) {
+                eprintln!("{lint}");
+            }
+            return Err(format!(
+                "{} bang lint finding(s) at {severity} or above, see {lint_path} or set {}=never",
+                failed.len(),
+                lint::FAIL_ON_VAR
+            )
+            .into());
+        }
+    }
+
+    for lint in &linter.lints {
+        *report.lint_counts.entry(lint.kind.to_string()).or_default() += 1;
+    }
+    report.lints = linter.at_least(Severity::Warning).cloned().collect();
+
+    let report_path = format!("{out_dir}/build_report.json");
+    println!("cargo::rustc-env=LSS_BUILD_REPORT={report_path}");
+    fs::write(report_path, serde_json::to_string(&report)?)?;
+
+    // kept out of the report so `/info` doesn't have to parse it
+    let upgraded_path = format!("{out_dir}/upgraded");
+    println!("cargo::rustc-env=LSS_UPGRADED={upgraded_path}");
+    fs::write(upgraded_path, upgraded.join("\n"))?;
 
-    let l = UtcDateTime::from(fs::metadata(BANG_PATH)?.modified()?);
+    let l = UtcDateTime::from(last_modified.ok_or("no bang sources given")?);
     let last_updated_path = format!("{out_dir}/last_updated");
     println!("cargo::rustc-env=LSS_LAST_UPDATED={last_updated_path}");
     fs::write(
@@ -94,6 +94,16 @@ in {
                 }
               ];
             }
+            {
+              template = "http://localhost:${toString cfg.port}/suggest";
+              type = "application/x-suggestions+json";
+              params = [
+                {
+                  name = "q";
+                  value = "{searchTerms}";
+                }
+              ];
+            }
           ];
         };
     };
@@ -0,0 +1,249 @@
+//! Restricts who can use the service when `broadcast = true`.
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tiny_http::{Header, Request};
+
+use crate::CONFIG;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denied {
+    /// The client isn't in `access.allow` or presented the wrong token.
+    Forbidden,
+    /// Basic auth is configured and the client should be asked for credentials.
+    Unauthorized,
+}
+
+/// Remembers the token, so links between the service's pages work without it in the URL.
+const TOKEN_COOKIE: &str = "lss_token";
+
+/// Check `request` against `[access]`, returning a `Set-Cookie` header to add to the response
+/// when the token was given in the URL.
+pub fn check(request: &Request) -> Result<Option<Header>, Denied> {
+    if !CONFIG.broadcast {
+        return Ok(None);
+    }
+
+    let access = &CONFIG.access;
+    let ip = request.remote_addr().map(|addr| addr.ip().to_canonical());
+
+    let allowed = access.allow.is_empty()
+        || ip.is_some_and(|ip| ip.is_loopback() || access.allow.iter().any(|c| c.contains(ip)));
+
+    if !allowed {
+        return Err(Denied::Forbidden);
+    }
+
+    if access.token.is_none() && access.username.is_none() {
+        return Ok(None);
+    }
+
+    if let Some(token) = &access.token {
+        // the cookie holds the token percent-encoded, so any token is a valid cookie value
+        let encoded = urlencoding::encode(token);
+
+        if cookie(request, TOKEN_COOKIE).is_some_and(|given| equal(given, &encoded)) {
+            return Ok(None);
+        }
+
+        let given = request
+            .url()
+            .split_once('?')
+            .and_then(|(_, query)| crate::query_param(query, "token"));
+
+        if given.is_some_and(|given| equal(&given, token)) {
+            let cookie = format!("{TOKEN_COOKIE}={encoded}; Path=/; HttpOnly; SameSite=Lax");
+            // unwrap: percent-encoding leaves only ASCII
+            return Ok(Some(Header::from_bytes("Set-Cookie", cookie).unwrap()));
+        }
+    }
+
+    if basic_auth_valid(request) {
+        Ok(None)
+    } else if access.username.is_some() {
+        Err(Denied::Unauthorized)
+    } else {
+        Err(Denied::Forbidden)
+    }
+}
+
+fn basic_auth_valid(request: &Request) -> bool {
+    let Some(username) = CONFIG.access.username.as_deref() else {
+        return false;
+    };
+    let password = CONFIG.access.password.as_deref().unwrap_or_default();
+
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .is_some_and(|credentials| equal(&credentials, &format!("{username}:{password}")))
+}
+
+/// The value of cookie `name` sent with `request`.
+fn cookie<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .filter(|h| h.field.equiv("Cookie"))
+        .flat_map(|h| h.value.as_str().split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Compare secrets in time that only depends on their lengths, not on where they differ.
+fn equal(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Whether `host`, from a `Host` header, names this machine rather than a domain someone else
+/// could point at it (DNS rebinding): an IP address, `localhost`, or a local network name.
+pub fn local_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => {
+            return rest
+                .split_once(']')
+                .is_some_and(|(ip, _)| ip.parse::<Ipv6Addr>().is_ok())
+        }
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+
+    !name.is_empty()
+        && (name.parse::<Ipv4Addr>().is_ok()
+            || !name.contains('.')
+            || name.ends_with(".localhost")
+            || name.ends_with(".local"))
+}
+
+/// An IP address range like `192.168.1.0/24`, or a single address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = s.split_once('/').unwrap_or((s, ""));
+
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid IP address '{addr}'"))?;
+
+        let max = match addr {
+            IpAddr::V4(_) => Ipv4Addr::BITS,
+            IpAddr::V6(_) => Ipv6Addr::BITS,
+        } as u8;
+
+        let prefix = if prefix.is_empty() {
+            max
+        } else {
+            prefix
+                .parse()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("invalid prefix length '{prefix}' in '{s}'"))?
+        };
+
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for Cidr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets() {
+        assert!(equal("secret", "secret"));
+        assert!(!equal("secret", "Secret"));
+        assert!(!equal("secre", "secret"));
+        assert!(!equal("", "secret"));
+        assert!(equal("", ""));
+    }
+
+    #[test]
+    fn local_hosts() {
+        for host in [
+            "localhost:9321",
+            "LOCALHOST",
+            "127.0.0.1:9321",
+            "192.168.1.10",
+            "[::1]:9322",
+            "desktop:9321",
+            "desktop.local.",
+            "lss.localhost",
+        ] {
+            assert!(local_host(host), "{host}");
+        }
+
+        for host in [
+            "",
+            ":9321",
+            "example.com",
+            "127.0.0.1.example.com:9321",
+            "[evil.com]",
+        ] {
+            assert!(!local_host(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn cidr() {
+        let net: Cidr = "192.168.1.0/24".parse().unwrap();
+        assert!(net.contains("192.168.1.42".parse().unwrap()));
+        assert!(!net.contains("192.168.2.1".parse().unwrap()));
+        assert!(!net.contains("::1".parse().unwrap()));
+
+        assert!("10.0.0.1/33".parse::<Cidr>().is_err());
+        assert_eq!("::1".parse::<Cidr>().unwrap().to_string(), "::1/128");
+    }
+}
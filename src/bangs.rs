@@ -0,0 +1,644 @@
+//! Readers for bang lists, shared with `build.rs`.
+//!
+//! Sources are given as `format:path`, like `kagi:bangs.json`, in a comma separated list where
+//! later sources override the shortcuts of earlier ones.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::engines::Segment;
+
+// `build.rs` includes this file by path, which would look for submodules next to it
+#[path = "bangs/js.rs"]
+mod js;
+
+/// Lists which sources `build.rs` reads into the built-in database.
+#[allow(dead_code)]
+pub const BUILD_SOURCES_VAR: &str = "LSS_BUILD_BANG_SOURCES";
+
+/// Lists sources to layer onto the built-in database at runtime.
+#[allow(dead_code)]
+pub const SOURCES_VAR: &str = "LSS_BANG_SOURCES";
+
+/// Stands for the database built into the binary when given at runtime.
+pub const BUILTIN_SOURCE: &str = "builtin";
+
+/// Upgrade `http://` URLs to HTTPS when building, `[https]` does the same at runtime.
+#[allow(dead_code)]
+pub const HTTPS_UPGRADE_VAR: &str = "LSS_BUILD_HTTPS_UPGRADE";
+
+/// Shortcuts to leave on plain HTTP when building, for sites that don't support HTTPS.
+#[allow(dead_code)]
+pub const HTTPS_EXCEPTIONS_VAR: &str = "LSS_BUILD_HTTPS_EXCEPTIONS";
+
+/// The HTTPS version of an `http://` URL.
+pub fn upgrade_https(url: &str) -> Option<String> {
+    url.strip_prefix("http://")
+        .map(|rest| format!("https://{rest}"))
+}
+
+/// Shortcuts from a comma separated list, or a TOML style one like `LSS_HTTPS_EXCEPTIONS` takes.
+#[allow(dead_code)]
+pub fn parse_shortcuts(list: &str) -> Vec<String> {
+    list.trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|s| s.trim().trim_matches(['"', '\'']).trim_start_matches('!'))
+        .filter(|s| !s.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// DuckDuckGo's `bang.js`.
+    DuckDuckGo,
+    /// Kagi's `bangs.json`, DuckDuckGo's schema plus `ts` aliases.
+    Kagi,
+    /// A JavaScript or TypeScript module holding DuckDuckGo style objects, like unduck's `bang.ts`.
+    Unduck,
+    /// `shortcut,name,url` rows, optionally followed by a category and subcategory.
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "duckduckgo" | "ddg" => Ok(Self::DuckDuckGo),
+            "kagi" => Ok(Self::Kagi),
+            "unduck" => Ok(Self::Unduck),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!(
+                "unknown bang format '{s}', expected duckduckgo, kagi, unduck or csv"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DuckDuckGo => "duckduckgo",
+            Self::Kagi => "kagi",
+            Self::Unduck => "unduck",
+            Self::Csv => "csv",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The database built into the binary, only meaningful at runtime.
+    Builtin,
+    File {
+        format: Format,
+        path: PathBuf,
+    },
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == BUILTIN_SOURCE {
+            return Ok(Self::Builtin);
+        }
+
+        let (format, path) = s
+            .split_once(':')
+            .ok_or_else(|| format!("bang source '{s}' should look like 'format:path'"))?;
+
+        Ok(Self::File {
+            format: format.parse()?,
+            path: path.into(),
+        })
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Builtin => f.write_str(BUILTIN_SOURCE),
+            Self::File { format, path } => write!(f, "{format}:{}", path.display()),
+        }
+    }
+}
+
+/// Parse a comma separated list of sources, lowest precedence first.
+pub fn parse_sources(list: &str) -> Result<Vec<Source>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// A single shortcut read from a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bang {
+    pub shortcut: String,
+    pub name: String,
+    /// URL template with `{s}` in place of the search terms, written out from `template`.
+    pub url: String,
+    pub template: Vec<Segment>,
+    /// Placeholders the source used that aren't supported, left in the URL as they are.
+    pub unsupported: Vec<String>,
+    pub category: Option<String>,
+    pub subcategory: Option<String>,
+}
+
+impl Bang {
+    /// Switch a plain `http://` URL to HTTPS, returning whether it was.
+    #[allow(dead_code)]
+    pub fn upgrade_https(&mut self) -> bool {
+        let Some(Segment::Literal(start)) = self.template.first_mut() else {
+            return false;
+        };
+        let Some(upgraded) = upgrade_https(start) else {
+            return false;
+        };
+
+        *start = upgraded.into();
+        self.url = Segment::to_url(&self.template);
+        true
+    }
+}
+
+/// Upgrades bangs to HTTPS when building, where an exception covers every alias of an engine.
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+pub struct HttpsUpgrade {
+    /// Lowercased shortcuts to leave on plain HTTP.
+    exceptions: Vec<String>,
+    /// URLs of the excepted engines, which aliases share even when listed as separate bangs.
+    excepted_urls: HashSet<String>,
+}
+
+#[allow(dead_code)]
+impl HttpsUpgrade {
+    pub fn new(exceptions: Vec<String>) -> Self {
+        Self {
+            exceptions,
+            excepted_urls: HashSet::new(),
+        }
+    }
+
+    /// Upgrade the bangs of one source, returning the shortcuts that were. Exceptions found in
+    /// earlier sources still apply.
+    pub fn apply(&mut self, bangs: &mut [Bang]) -> Vec<String> {
+        self.excepted_urls.extend(
+            bangs
+                .iter()
+                .filter(|bang| self.exceptions.contains(&bang.shortcut.to_lowercase()))
+                .map(|bang| bang.url.clone()),
+        );
+
+        bangs
+            .iter_mut()
+            .filter(|bang| !self.excepted_urls.contains(&bang.url))
+            .filter_map(|bang| bang.upgrade_https().then(|| bang.shortcut.clone()))
+            .collect()
+    }
+}
+
+/// The bangs of a source, and the entries left out or changed on the way.
+#[derive(Debug, Default)]
+pub struct Bangs {
+    pub bangs: Vec<Bang>,
+    /// Shortcuts, or line numbers, of entries that couldn't be used.
+    pub skipped: Vec<String>,
+    /// Shortcuts whose URL needed more than the usual `{{{s}}}` to `{s}` rewriting.
+    pub rewritten: Vec<String>,
+}
+
+/// How reading one source went, written by `build.rs` so `dump` can show it later.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SourceReport {
+    pub source: String,
+    pub bangs: usize,
+    pub skipped: Vec<String>,
+    pub rewritten: Vec<String>,
+}
+
+/// What `build.rs` found, written next to the database and embedded in the binary.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BuildReport {
+    pub sources: Vec<SourceReport>,
+    /// Number of lint findings of each kind.
+    pub lint_counts: BTreeMap<String, usize>,
+    /// Findings at warning level or above, every finding is only in `lint_report.txt`.
+    pub lints: Vec<crate::lint::Lint>,
+}
+
+impl SourceReport {
+    #[allow(dead_code)]
+    pub fn new(source: &Source, bangs: &Bangs) -> Self {
+        Self {
+            source: source.to_string(),
+            bangs: bangs.bangs.len(),
+            skipped: bangs.skipped.clone(),
+            rewritten: bangs.rewritten.clone(),
+        }
+    }
+}
+
+pub fn read(format: Format, path: &PathBuf) -> Result<Bangs, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("failed to read {path:?}: {e}"))?;
+    parse(format, &text).map_err(|e| format!("failed to parse {path:?}: {e}"))
+}
+
+pub fn parse(format: Format, text: &str) -> Result<Bangs, String> {
+    match format {
+        Format::DuckDuckGo | Format::Kagi => {
+            parse_duckduckgo(serde_json::from_str(text).map_err(|e| e.to_string())?)
+        }
+        Format::Unduck => parse_duckduckgo(
+            serde_json::from_value(js::module_array(text)?).map_err(|e| e.to_string())?,
+        ),
+        Format::Csv => parse_csv(text),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct DuckDuckGoBang {
+    #[serde(rename = "u")]
+    url: String,
+    #[serde(rename = "s")]
+    name: String,
+    #[serde(rename = "t")]
+    shortcut: String,
+    #[serde(rename = "ts", default)]
+    aliases: Vec<String>,
+    #[serde(rename = "c")]
+    category: Option<String>,
+    #[serde(rename = "sc")]
+    subcategory: Option<String>,
+}
+
+fn parse_duckduckgo(parsed: Vec<DuckDuckGoBang>) -> Result<Bangs, String> {
+    let mut bangs = Bangs::default();
+
+    for parse in parsed {
+        // shortcuts to the duckduckgo website itself
+        if parse.url.starts_with("/") {
+            bangs.skipped.push(parse.shortcut);
+            continue;
+        }
+
+        let (template, unsupported) = parse_duckduckgo_template(&parse.url);
+        let url = Segment::to_url(&template);
+
+        if url != parse.url.replace("{{{s}}}", "{s}") {
+            bangs.rewritten.push(parse.shortcut.clone());
+        }
+
+        for shortcut in [parse.shortcut].into_iter().chain(parse.aliases) {
+            bangs.bangs.push(Bang {
+                shortcut,
+                name: parse.name.clone(),
+                url: url.clone(),
+                template: template.clone(),
+                unsupported: unsupported.clone(),
+                category: parse.category.clone(),
+                subcategory: parse.subcategory.clone(),
+            });
+        }
+    }
+
+    Ok(bangs)
+}
+
+/// Split a DuckDuckGo URL template into literal text and placeholders, along with the names of
+/// any placeholders that aren't supported.
+///
+/// The search terms are `{{{s}}}`, and other placeholders are single braced names like
+/// `{inputEncoding}`. Every other brace is literal, e.g. in JSON passed as a parameter.
+pub fn parse_duckduckgo_template(url: &str) -> (Vec<Segment>, Vec<String>) {
+    let mut template = Vec::new();
+    let mut unsupported = Vec::new();
+    let mut literal = String::new();
+    let mut rest = url;
+
+    while let Some(c) = rest.chars().next() {
+        let placeholder = if let Some(after) = rest.strip_prefix("{{{s}}}") {
+            Some((Segment::Terms, after))
+        } else if let Some((name, after)) = rest
+            .strip_prefix('{')
+            .and_then(|inner| inner.split_once('}'))
+            .filter(|(name, _)| is_placeholder_name(name))
+        {
+            match name {
+                "inputEncoding" => Some((Segment::InputEncoding, after)),
+                _ => {
+                    unsupported.push(format!("{{{name}}}"));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        match placeholder {
+            Some((segment, after)) => {
+                if !literal.is_empty() {
+                    template.push(Segment::Literal(literal.as_str().into()));
+                    literal.clear();
+                }
+                template.push(segment);
+                rest = after;
+            }
+            None => {
+                literal.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    if !literal.is_empty() {
+        template.push(Segment::Literal(literal.into()));
+    }
+
+    (template, unsupported)
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_csv(text: &str) -> Result<Bangs, String> {
+    let mut bangs = Bangs::default();
+
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = csv_fields(line);
+
+        if idx == 0
+            && fields
+                .first()
+                .is_some_and(|f| f.eq_ignore_ascii_case("shortcut"))
+        {
+            continue;
+        }
+
+        let [shortcut, name, url, rest @ ..] = fields.as_slice() else {
+            return Err(format!("line {}: expected shortcut,name,url", idx + 1));
+        };
+
+        if shortcut.is_empty() || url.is_empty() {
+            bangs.skipped.push(format!("line {}", idx + 1));
+            continue;
+        }
+
+        let optional = |field: Option<&String>| field.filter(|f| !f.is_empty()).cloned();
+
+        bangs.bangs.push(Bang {
+            shortcut: shortcut.trim_start_matches('!').to_string(),
+            name: name.clone(),
+            url: url.clone(),
+            template: Segment::parse(url),
+            unsupported: Vec::new(),
+            category: optional(rest.first()),
+            subcategory: optional(rest.get(1)),
+        });
+    }
+
+    Ok(bangs)
+}
+
+/// Split a CSV line, where fields may be quoted and `""` is a literal quote.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        // unwrap: starts with one field and only ever grows
+        let field = fields.last_mut().unwrap();
+
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+
+    fields.iter().map(|f| f.trim().to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcuts(bangs: &Bangs) -> Vec<&str> {
+        bangs.bangs.iter().map(|b| b.shortcut.as_str()).collect()
+    }
+
+    #[test]
+    fn duckduckgo_templates() {
+        let literal = |text: &str| Segment::Literal(text.into());
+
+        // from bang.json
+        let cases: &[(&str, &[Segment], &[&str])] = &[
+            (
+                "https://www.google.com/search?q={{{s}}}",
+                &[literal("https://www.google.com/search?q="), Segment::Terms],
+                &[],
+            ),
+            (
+                "http://{{{s}}}.deviantart.com",
+                &[
+                    literal("http://"),
+                    Segment::Terms,
+                    literal(".deviantart.com"),
+                ],
+                &[],
+            ),
+            (
+                "http://search.naver.com/search.naver?ie={inputEncoding}&query={{{s}}}",
+                &[
+                    literal("http://search.naver.com/search.naver?ie="),
+                    Segment::InputEncoding,
+                    literal("&query="),
+                    Segment::Terms,
+                ],
+                &[],
+            ),
+            (
+                "https://www.congress.gov/search?q={%22search%22%3A%22{{{s}}}%22}",
+                &[
+                    literal("https://www.congress.gov/search?q={%22search%22%3A%22"),
+                    Segment::Terms,
+                    literal("%22}"),
+                ],
+                &[],
+            ),
+            (
+                "http://www.edbpriser.dk/Search/General.aspx?q={{{s}}}}",
+                &[
+                    literal("http://www.edbpriser.dk/Search/General.aspx?q="),
+                    Segment::Terms,
+                    literal("}"),
+                ],
+                &[],
+            ),
+            // not in bang.json, but sources may use other placeholders
+            (
+                "https://example.org/{lang}/?q={{{s}}}",
+                &[literal("https://example.org/{lang}/?q="), Segment::Terms],
+                &["{lang}"],
+            ),
+        ];
+
+        for (url, template, unsupported) in cases {
+            let (parsed, parsed_unsupported) = parse_duckduckgo_template(url);
+            assert_eq!(parsed, *template, "{url}");
+            assert_eq!(parsed_unsupported, *unsupported, "{url}");
+        }
+    }
+
+    #[test]
+    fn https_exceptions_cover_aliases() {
+        let mut bangs = parse(
+            Format::DuckDuckGo,
+            r#"[
+                {"s": "TFD", "t": "define", "u": "http://www.thefreedictionary.com/{{{s}}}"},
+                {"s": "TFD", "t": "tfd", "u": "http://www.thefreedictionary.com/{{{s}}}"},
+                {"s": "Maps", "t": "gmap", "u": "http://maps.google.com/maps?q={{{s}}}"},
+                {"s": "Secure", "t": "sec", "u": "https://example.org/?q={{{s}}}"}
+            ]"#,
+        )
+        .unwrap()
+        .bangs;
+
+        let mut https = HttpsUpgrade::new(parse_shortcuts("!TFD"));
+        assert_eq!(https.apply(&mut bangs), ["gmap"]);
+        assert_eq!(bangs[0].url, "http://www.thefreedictionary.com/{s}");
+        assert_eq!(bangs[2].url, "https://maps.google.com/maps?q={s}");
+
+        // a later source's alias is still covered
+        let mut later = parse(
+            Format::Csv,
+            "dict,TFD,http://www.thefreedictionary.com/{s}\nm,Maps,http://maps.example/{s}",
+        )
+        .unwrap()
+        .bangs;
+        assert_eq!(https.apply(&mut later), ["m"]);
+    }
+
+    #[test]
+    fn csv() {
+        let bangs = parse(
+            Format::Csv,
+            "shortcut,name,url\n\
+             # a comment\n\
+             !g,Google,https://www.google.com/search?q={s},Search,Web\n\
+             \n\
+             \"w\",\"Wikipedia, English\",https://en.wikipedia.org/?search={s}\n\
+             q,\"Say \"\"hi\"\"\",https://example.org/?q={s},,\n\
+             ,Empty,https://example.org/\n",
+        )
+        .unwrap();
+
+        assert_eq!(shortcuts(&bangs), ["g", "w", "q"]);
+        assert_eq!(bangs.skipped, ["line 7"]);
+        assert_eq!(bangs.bangs[0].category.as_deref(), Some("Search"));
+        assert_eq!(bangs.bangs[0].subcategory.as_deref(), Some("Web"));
+        assert_eq!(bangs.bangs[1].name, "Wikipedia, English");
+        assert_eq!(bangs.bangs[1].category, None);
+        assert_eq!(bangs.bangs[2].name, "Say \"hi\"");
+        assert_eq!(bangs.bangs[2].category, None);
+        assert_eq!(
+            bangs.bangs[2].template,
+            [
+                Segment::Literal("https://example.org/?q=".into()),
+                Segment::Terms
+            ]
+        );
+
+        assert!(parse(Format::Csv, "g,Google\n").is_err());
+    }
+
+    #[test]
+    fn kagi() {
+        let bangs = parse(
+            Format::Kagi,
+            r#"[
+                {"s": "Google", "d": "www.google.com", "t": "g", "ts": ["goog"],
+                 "u": "https://www.google.com/search?q={{{s}}}", "c": "Online Services"},
+                {"s": "DuckDuckGo", "t": "ddg", "u": "/?q={{{s}}}"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(shortcuts(&bangs), ["g", "goog"]);
+        assert_eq!(bangs.skipped, ["ddg"]);
+        assert_eq!(bangs.bangs[1].url, "https://www.google.com/search?q={s}");
+        assert_eq!(bangs.bangs[1].category.as_deref(), Some("Online Services"));
+    }
+
+    #[test]
+    fn unduck() {
+        let bangs = parse(
+            Format::Unduck,
+            r#"// generated from https://duckduckgo.com/bang.js
+import type { Bang } from "./types";
+
+/* a [ in a comment */
+export const bangs: Bang[] = [
+  {
+    c: "Tech",
+    d: "www.01net.com",
+    r: 0,
+    s: '01net [fr]',
+    sc: "Downloads (apps)",
+    t: "01net",
+    u: "http://www.01net.com/cgi-bin/search?q={{{s}}}", // trailing comment
+  },
+  {
+    s: 'It\'s é \u{1F600} 😀',
+    t: "x",
+    u: 'https://example.org/?q={{{s}}}&c="]"',
+  },
+];
+
+export default bangs;
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(shortcuts(&bangs), ["01net", "x"]);
+        assert_eq!(bangs.bangs[0].name, "01net [fr]");
+        assert_eq!(
+            bangs.bangs[0].subcategory.as_deref(),
+            Some("Downloads (apps)")
+        );
+        assert_eq!(bangs.bangs[1].name, "It's é 😀 😀");
+        assert_eq!(bangs.bangs[1].url, "https://example.org/?q={s}&c=\"]\"");
+    }
+
+    #[test]
+    fn unduck_default_export() {
+        let bangs = parse(
+            Format::Unduck,
+            "export default [{ t: 'a', s: 'A', u: 'https://a.example/{{{s}}}' }]",
+        )
+        .unwrap();
+
+        assert_eq!(shortcuts(&bangs), ["a"]);
+    }
+}
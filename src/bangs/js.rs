@@ -0,0 +1,321 @@
+//! Just enough of a JavaScript reader to take the bangs out of a module like unduck's `bang.ts`.
+
+/// The array assigned or default exported in a JavaScript or TypeScript module, like
+/// `export const bangs: Bang[] = [...]`.
+///
+/// Only literals are supported: objects, arrays, single or double quoted strings, numbers,
+/// `true`, `false` and `null`, with comments and trailing commas. Anything else is an error
+/// rather than a guess.
+pub fn module_array(text: &str) -> Result<serde_json::Value, String> {
+    let mut js = Js {
+        chars: text.chars().collect(),
+        idx: 0,
+    };
+    // `[` also shows up in type annotations like `Bang[]`, so only take one after `=` or `default`
+    let mut previous = String::new();
+
+    loop {
+        js.skip_trivia()?;
+
+        match js.peek() {
+            None => return Err("no array assigned or exported".into()),
+            Some('[') if previous == "=" || previous == "default" => return js.value(),
+            Some('"' | '\'') => previous = js.string()?,
+            Some(c) if c.is_alphabetic() || c == '_' || c == '$' => previous = js.ident(),
+            Some(c) => {
+                js.idx += 1;
+                previous = c.to_string();
+            }
+        }
+    }
+}
+
+/// A reader for the literals in a JavaScript module.
+struct Js {
+    chars: Vec<char>,
+    idx: usize,
+}
+
+impl Js {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.idx).copied()
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.chars[..self.idx]
+            .iter()
+            .filter(|&&c| c == '\n')
+            .count()
+            + 1;
+        format!("line {line}: {message}")
+    }
+
+    /// Skip whitespace and comments.
+    fn skip_trivia(&mut self) -> Result<(), String> {
+        loop {
+            match (self.peek(), self.chars.get(self.idx + 1)) {
+                (Some(c), _) if c.is_whitespace() => self.idx += 1,
+                (Some('/'), Some('/')) => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.idx += 1;
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    let start = self.idx;
+                    self.idx += 2;
+                    while !(self.peek() == Some('*') && self.chars.get(self.idx + 1) == Some(&'/'))
+                    {
+                        if self.peek().is_none() {
+                            self.idx = start;
+                            return Err(self.error("unclosed comment"));
+                        }
+                        self.idx += 1;
+                    }
+                    self.idx += 2;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn ident(&mut self) -> String {
+        let start = self.idx;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        {
+            self.idx += 1;
+        }
+        self.chars[start..self.idx].iter().collect()
+    }
+
+    fn value(&mut self) -> Result<serde_json::Value, String> {
+        self.skip_trivia()?;
+
+        match self.peek() {
+            Some('[') => {
+                self.idx += 1;
+                let mut array = Vec::new();
+                while !self.close(']')? {
+                    array.push(self.value()?);
+                }
+                Ok(array.into())
+            }
+            Some('{') => {
+                self.idx += 1;
+                let mut object = serde_json::Map::new();
+                while !self.close('}')? {
+                    let key = match self.peek() {
+                        Some('"' | '\'') => self.string()?,
+                        Some(c) if c.is_alphabetic() || c == '_' || c == '$' => self.ident(),
+                        _ => return Err(self.error("expected a property name")),
+                    };
+                    self.skip_trivia()?;
+                    if self.peek() != Some(':') {
+                        return Err(self.error(&format!("expected ':' after '{key}'")));
+                    }
+                    self.idx += 1;
+                    object.insert(key, self.value()?);
+                }
+                Ok(object.into())
+            }
+            Some('"' | '\'') => Ok(self.string()?.into()),
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let start = self.idx;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_digit() || ".eE+-".contains(c))
+                {
+                    self.idx += 1;
+                }
+                let number: String = self.chars[start..self.idx].iter().collect();
+                serde_json::from_str(&number).map_err(|_| {
+                    self.idx = start;
+                    self.error(&format!("unsupported number '{number}'"))
+                })
+            }
+            Some(c) if c.is_alphabetic() => {
+                let start = self.idx;
+                match self.ident().as_str() {
+                    "true" => Ok(true.into()),
+                    "false" => Ok(false.into()),
+                    "null" => Ok(serde_json::Value::Null),
+                    ident => {
+                        let message = format!("'{ident}' isn't a literal");
+                        self.idx = start;
+                        Err(self.error(&message))
+                    }
+                }
+            }
+            Some('`') => Err(self.error("template literals aren't supported")),
+            Some(c) => Err(self.error(&format!("unexpected '{c}'"))),
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    /// Whether the array or object ends here, after skipping the comma between two items.
+    fn close(&mut self, end: char) -> Result<bool, String> {
+        self.skip_trivia()?;
+        if self.peek() == Some(',') {
+            self.idx += 1;
+            self.skip_trivia()?;
+        }
+        if self.peek() == Some(end) {
+            self.idx += 1;
+            return Ok(true);
+        }
+        if self.peek().is_none() {
+            return Err(self.error(&format!("missing '{end}'")));
+        }
+        Ok(false)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let start = self.idx;
+        // unwrap: only called on a quote
+        let quote = self.peek().unwrap();
+        self.idx += 1;
+        let mut string = String::new();
+
+        loop {
+            let Some(c) = self.peek() else {
+                self.idx = start;
+                return Err(self.error("unclosed string"));
+            };
+            self.idx += 1;
+
+            match c {
+                c if c == quote => return Ok(string),
+                '\n' => {
+                    self.idx = start;
+                    return Err(self.error("unclosed string"));
+                }
+                '\\' => {
+                    let Some(escaped) = self.peek() else {
+                        continue;
+                    };
+                    self.idx += 1;
+                    match escaped {
+                        'n' => string.push('\n'),
+                        't' => string.push('\t'),
+                        'r' => string.push('\r'),
+                        'b' => string.push('\u{8}'),
+                        'f' => string.push('\u{c}'),
+                        'v' => string.push('\u{b}'),
+                        '0' => string.push('\0'),
+                        '\n' => {}
+                        'x' | 'u' => string.push(self.code_point(escaped)?),
+                        c => string.push(c),
+                    }
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    /// The character of a `\xHH`, `\uHHHH` or `\u{H...}` escape, after its letter, joining
+    /// UTF-16 surrogate pairs.
+    fn code_point(&mut self, kind: char) -> Result<char, String> {
+        let start = self.idx;
+        let mut value = if kind == 'u' && self.peek() == Some('{') {
+            let end = self.chars[self.idx..]
+                .iter()
+                .position(|&c| c == '}')
+                .ok_or_else(|| self.error("unclosed unicode escape"))?;
+            let value = self.hex(self.idx + 1, end - 1);
+            self.idx += end + 1;
+            value
+        } else {
+            let len = if kind == 'x' { 2 } else { 4 };
+            let value = self.hex(self.idx, len);
+            self.idx += len;
+            value
+        };
+
+        if let Some(high @ 0xd800..=0xdbff) = value {
+            if self.chars.get(self.idx..self.idx + 2) == Some(&['\\', 'u']) {
+                if let Some(low @ 0xdc00..=0xdfff) = self.hex(self.idx + 2, 4) {
+                    value = Some(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00));
+                    self.idx += 6;
+                }
+            }
+        }
+
+        value.and_then(char::from_u32).ok_or_else(|| {
+            self.idx = start;
+            self.error(&format!("invalid '\\{kind}' escape"))
+        })
+    }
+
+    fn hex(&self, start: usize, len: usize) -> Option<u32> {
+        let digits: String = self.chars.get(start..start + len)?.iter().collect();
+        u32::from_str_radix(&digits, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        let value = module_array(
+            r#"import type { Bang } from "./types";
+
+/* an [ in a comment */
+export const bangs: Bang[] = [
+  // a comment between elements
+  { "quoted": 'single', bare: "double", $x: -1.5e3, t: true, f: false, n: null, },
+  ['\x41\u0042\u{1F600}\n', "it's", 'say "hi"',],
+];"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!([
+                { "quoted": "single", "bare": "double", "$x": -1500.0, "t": true, "f": false, "n": null },
+                ["AB\u{1F600}\n", "it's", "say \"hi\""],
+            ])
+        );
+    }
+
+    #[test]
+    fn assignment_or_default_export() {
+        assert_eq!(
+            module_array("const x: Bang[] = [1]; export default [2];").unwrap(),
+            serde_json::json!([1])
+        );
+        assert_eq!(
+            module_array("type X = 'a[]'; export default [2];").unwrap(),
+            serde_json::json!([2])
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let error = |text: &str| module_array(text).unwrap_err();
+
+        assert_eq!(error("const x = 1;"), "no array assigned or exported");
+        assert_eq!(
+            error("export const bangs = [\n  { t: `a`, s: 'A', u: 'x' },\n];"),
+            "line 2: template literals aren't supported"
+        );
+        assert_eq!(
+            error("export const bangs = [...other];"),
+            "line 1: unexpected '.'"
+        );
+        assert_eq!(
+            error("export const bangs = [{ t: name }];"),
+            "line 1: 'name' isn't a literal"
+        );
+        assert_eq!(
+            error("export const bangs = [{ t: 'a }];"),
+            "line 1: unclosed string"
+        );
+        assert_eq!(
+            error("export const bangs = [{ t: 'a' }"),
+            "line 1: missing ']'"
+        );
+    }
+}
@@ -1,32 +1,49 @@
 use std::{
-    collections::HashMap,
-    fs, iter,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fmt, fs,
     net::{Ipv4Addr, SocketAddr},
-    path::PathBuf,
-    sync::LazyLock,
+    path::{Path, PathBuf},
+    sync::{LazyLock, OnceLock},
 };
 
 use compact_str::{CompactString, ToCompactString};
 
-use crate::engines::{default, InternalSearchEngine, SearchEngineDatabase, SearchEngineRef};
+use crate::{
+    access::Cidr,
+    engines::{
+        default, InternalSearchEngine, SearchEngineDatabase, SearchEngineRef, SearchGroup, Segment,
+    },
+    rules::{Rule, Target},
+};
+
+/// A config file passed with `--config`, set before [`CONFIG`] is first used.
+pub static CLI_CONFIG: OnceLock<PathBuf> = OnceLock::new();
 
-pub static CONFIG_CHECKS: LazyLock<Vec<PathBuf>> = LazyLock::new(|| {
-    dirs::config_dir()
-        .into_iter()
-        .map(|dir| dir.join("local-search-shortcuts/config.toml"))
-        .chain(iter::once("local-search-shortcuts.toml".into()))
-        .collect()
+/// Where each layer is read from, lowest precedence first.
+pub static CONFIG_CHECKS: LazyLock<Vec<(Layer, PathBuf)>> = LazyLock::new(|| {
+    let system = cfg!(unix).then(|| PathBuf::from("/etc/local-search-shortcuts/config.toml"));
+    let user = dirs::config_dir().map(|dir| dir.join("local-search-shortcuts/config.toml"));
+
+    [
+        (Layer::System, system),
+        (Layer::User, user),
+        (Layer::Project, Some("local-search-shortcuts.toml".into())),
+        (
+            Layer::Environment,
+            env::var_os("LSS_CONFIG").map(PathBuf::from),
+        ),
+        (Layer::CommandLine, CLI_CONFIG.get().cloned()),
+    ]
+    .into_iter()
+    .filter_map(|(layer, path)| Some((layer, path?)))
+    .collect()
 });
 
 pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
-    let config = CONFIG_CHECKS
-        .iter()
-        .find_map(Config::from_file)
-        .unwrap_or_default();
-
-    if let Some(ref path) = config.path {
-        tracing::info!("loaded config file {path:?}");
-    } else {
+    let config = Config::load();
+
+    if config.layers.is_empty() {
         tracing::info!("no config file found, using defaults");
     }
 
@@ -35,13 +52,66 @@ pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
     config
 });
 
+/// Where a config file came from, later layers override earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    System,
+    User,
+    /// `./local-search-shortcuts.toml`, only read when there's no system or user config file.
+    Project,
+    /// The `LSS_CONFIG` environment variable.
+    Environment,
+    /// `LSS_PORT`, `LSS_ENGINE_<NAME>` and other variables overriding single settings.
+    Variables,
+    /// `--config` on the command line.
+    CommandLine,
+}
+
+impl Layer {
+    /// Layers the user explicitly pointed at have to exist.
+    fn required(self) -> bool {
+        matches!(self, Self::Environment | Self::CommandLine)
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Project => "project",
+            Self::Environment => "LSS_CONFIG",
+            Self::Variables => "environment",
+            Self::CommandLine => "--config",
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub port: u16,
     pub default_engine: OwnedSearchEngine,
     pub broadcast: bool,
+    pub search_paths: Vec<String>,
+    pub query_params: Vec<String>,
     pub engines: SearchEngineDatabase,
-    pub path: Option<PathBuf>,
+    pub rules: Vec<Rule>,
+    pub navigation: NavigationConfig,
+    pub https: HttpsConfig,
+    pub stats: StatsConfig,
+    pub history: HistoryConfig,
+    pub access: AccessConfig,
+    pub tls: TlsConfig,
+    /// The file loaded for each layer, lowest precedence first.
+    pub layers: Vec<(Layer, PathBuf)>,
+    /// Every file that contributed to the config, including ones pulled in with `include`.
+    pub files: Vec<PathBuf>,
+    /// `LSS_*` environment variables that were applied, and the setting each one overrides.
+    pub variables: Vec<(String, String)>,
+    /// The layers that set each setting, like `port` or `engines.homemanager`.
+    pub origins: BTreeMap<String, Vec<Layer>>,
+    /// Problems found while loading, shown on the main page.
+    pub errors: Vec<String>,
 }
 
 impl Default for Config {
@@ -49,10 +119,23 @@ impl Default for Config {
         Config {
             port: default::port(),
             broadcast: false,
+            search_paths: default::search_paths(),
+            query_params: default::query_params(),
             // unwrap: asserted in build.rs that default engine is present
             default_engine: force_clone(&crate::ENGINES.get_engine(&default::engine()).unwrap()),
             engines: SearchEngineDatabase::default(),
-            path: None,
+            rules: Vec::new(),
+            navigation: NavigationConfig::default(),
+            https: HttpsConfig::default(),
+            stats: StatsConfig::default(),
+            history: HistoryConfig::default(),
+            access: AccessConfig::default(),
+            tls: TlsConfig::default(),
+            layers: Vec::new(),
+            files: Vec::new(),
+            variables: Vec::new(),
+            origins: BTreeMap::new(),
+            errors: Vec::new(),
         }
     }
 }
@@ -68,54 +151,544 @@ impl Config {
         (ip, self.port).into()
     }
 
-    fn from_file(path: &PathBuf) -> Option<Self> {
-        let file = match String::from_utf8(fs::read(path).ok()?)
-            .map_err(|e| e.to_string())
-            .and_then(|t| toml::from_str::<ConfigFile>(&t).map_err(|e| e.to_string()))
-        {
-            Ok(text) => text,
+    pub fn tls_addr(&self) -> SocketAddr {
+        (self.addr().ip(), self.tls.port).into()
+    }
+
+    /// The address browsers should use, preferring HTTPS when it's enabled.
+    pub fn base_url(&self) -> String {
+        if self.tls.enabled() {
+            format!("https://localhost:{}", self.tls.port)
+        } else {
+            format!("http://localhost:{}", self.port)
+        }
+    }
+
+    /// Read and merge every layer in [`CONFIG_CHECKS`].
+    fn load() -> Self {
+        let mut merged = toml::Table::new();
+        let mut loader = Loader::default();
+        let mut layers = Vec::new();
+        let mut origins = BTreeMap::new();
+
+        let mut variables = Vec::new();
+        let mut found_global = false;
+
+        // environment variables sit between the files and `--config`
+        let mut sources: Vec<(Layer, Option<&PathBuf>)> = CONFIG_CHECKS
+            .iter()
+            .map(|(layer, path)| (*layer, Some(path)))
+            .chain([(Layer::Variables, None)])
+            .collect();
+        sources.sort_by_key(|(layer, _)| *layer);
+
+        for (layer, path) in sources {
+            let Some(path) = path else {
+                let table;
+                (table, variables) = variables_table(&mut loader);
+                record_origins(&mut origins, &table, layer);
+                merge_tables(&mut merged, table);
+                continue;
+            };
+
+            if !path.exists() && !layer.required() {
+                continue;
+            }
+
+            // the project file is a fallback for when nothing is configured globally
+            match layer {
+                Layer::System | Layer::User => found_global = true,
+                Layer::Project if found_global => {
+                    tracing::info!("ignoring project config file {path:?}, a global one exists");
+                    continue;
+                }
+                _ => {}
+            }
+
+            let read_before = loader.files.len();
+
+            // check each layer on its own so errors point at the right file
+            let table = loader.load(path).and_then(|table| {
+                table
+                    .clone()
+                    .try_into::<ConfigFile>()
+                    .map(|_| table)
+                    .map_err(|e| e.to_string())
+            });
+
+            match table {
+                Ok(table) => {
+                    crate::metrics::record_config_file(true);
+                    tracing::info!("loaded {layer} config file {path:?}");
+
+                    record_origins(&mut origins, &table, layer);
+                    merge_tables(&mut merged, table);
+                    layers.push((layer, path.canonicalize().unwrap_or(path.clone())));
+                }
+                Err(err) => {
+                    crate::metrics::record_config_file(false);
+                    loader.files.truncate(read_before);
+                    loader.error(format!("skipped {layer} config file {path:?}: {err}"));
+                }
+            }
+        }
+
+        let file = match merged.try_into::<ConfigFile>() {
+            Ok(file) => file,
             Err(err) => {
-                tracing::warn!("failed to parse config file {path:?}: {err}");
-                return None;
+                loader.error(format!("merged config is invalid, using defaults: {err}"));
+                return Self {
+                    errors: loader.errors,
+                    ..Self::default()
+                };
             }
         };
 
-        let path = path.canonicalize().unwrap_or(path.clone());
+        let config = Self::from_config_file(file, &mut loader.errors);
+
+        Self {
+            layers,
+            files: loader.files,
+            variables,
+            origins,
+            errors: loader.errors,
+            ..config
+        }
+    }
+
+    fn from_config_file(file: ConfigFile, errors: &mut Vec<String>) -> Self {
+        let mut warn = |message: String| {
+            tracing::warn!("{message}");
+            errors.push(message);
+        };
+
+        let tls = file.tls;
 
         let mut engines = SearchEngineDatabase::default();
 
-        for (name, url) in file.engines {
+        let mut sites = Vec::new();
+        let mut groups = Vec::new();
+
+        for (name, engine) in file.engines {
+            match engine {
+                EngineFile::Url(url) => engines.insert(
+                    &name.clone().into(),
+                    InternalSearchEngine {
+                        name: name.into(),
+                        url: url.into(),
+                        category: Some("Custom".into()),
+                        subcategory: None,
+                    },
+                ),
+                EngineFile::Site { site } => sites.push((name, site)),
+                EngineFile::Group(members) => groups.push((name, members)),
+                EngineFile::Enabled(false) => {}
+                EngineFile::Enabled(true) => warn(format!(
+                    "skipping engine '{name}', it needs a URL, site or group"
+                )),
+            }
+        }
+
+        let mut default_engine = force_clone(
+            &engines
+                .get_engine(&file.default)
+                .or(crate::ENGINES.get_engine(&file.default))
+                .unwrap_or_else(|| {
+                    warn(format!(
+                        "config's default engine '{}' not found, using {}",
+                        file.default,
+                        default::engine()
+                    ));
+                    // unwrap: asserted in build.rs that default engine is present
+                    crate::ENGINES.get_engine(&default::engine()).unwrap()
+                }),
+        );
+
+        if engines.get_engine(&file.default).is_none() {
+            default_engine.url = file.https.url(&file.default, &default_engine.url).into();
+        }
+
+        // site engines search the default engine, so they need it resolved first
+        for (name, site) in sites {
+            let site = site.trim();
+
+            if site.is_empty() || site.contains(char::is_whitespace) {
+                warn(format!(
+                    "skipping site engine '{name}', '{site}' isn't a domain"
+                ));
+                continue;
+            }
+
+            if !default_engine.url.contains("{s}") {
+                warn(format!(
+                    "skipping site engine '{name}', the default engine doesn't take search terms"
+                ));
+                continue;
+            }
+
             engines.insert(
                 &name.clone().into(),
                 InternalSearchEngine {
                     name: name.into(),
-                    url: url.into(),
+                    url: site_url(&default_engine.url, site).into(),
                     category: Some("Custom".into()),
                     subcategory: None,
                 },
             );
         }
 
-        let default_engine = engines
-            .get_engine(&file.default)
-            .or(crate::ENGINES.get_engine(&file.default))
-            .unwrap_or_else(|| {
-                tracing::warn!(
-                    "config's default engine '{}' not found, using {}",
-                    file.default,
-                    default::engine()
-                );
-                // unwrap: asserted in build.rs that default engine is present
-                crate::ENGINES.get_engine(&default::engine()).unwrap()
-            });
+        // members can be any engine, custom or built-in, but not another group
+        for (name, members) in groups {
+            let members: Vec<CompactString> = members
+                .into_iter()
+                .map(|member| member.trim_start_matches('!').to_lowercase().into())
+                .filter(|member: &CompactString| {
+                    let found = engines.get_engine(member).is_some()
+                        || crate::ENGINES.get_engine(member).is_some();
+                    if !found {
+                        warn(format!(
+                            "skipping member '{member}' of group '{name}', no such engine"
+                        ));
+                    }
+                    found
+                })
+                .collect();
+
+            if members.is_empty() {
+                warn(format!("skipping group '{name}', it has no valid members"));
+                continue;
+            }
 
-        Some(Self {
+            if crate::ENGINES.get_engine(&name).is_some() || engines.get_engine(&name).is_some() {
+                warn(format!(
+                    "group '{name}' is hidden by an engine with the same shortcut"
+                ));
+            }
+
+            engines.insert_group(
+                &name.clone().into(),
+                SearchGroup {
+                    name: name.into(),
+                    members,
+                },
+            );
+        }
+
+        let rules = file
+            .rules
+            .into_iter()
+            .filter_map(|rule| {
+                let pattern = regex::Regex::new(&rule.pattern)
+                    .inspect_err(|e| warn(format!("skipping rule with invalid pattern: {e}")))
+                    .ok()?;
+
+                let target = match (rule.engine, rule.url) {
+                    (Some(shortcut), None) => {
+                        if let Some(engine) = engines.get_engine(&shortcut) {
+                            Target::Engine {
+                                name: engine.name.to_string(),
+                                template: engines
+                                    .get_template(&shortcut)
+                                    .unwrap_or_else(|| Segment::parse(engine.url)),
+                            }
+                        } else if let Some(engine) = crate::ENGINES.get_engine(&shortcut) {
+                            let template = crate::ENGINES
+                                .get_template(&shortcut)
+                                .unwrap_or_else(|| Segment::parse(engine.url));
+                            let url = Segment::to_url(&template);
+
+                            Target::Engine {
+                                name: engine.name.to_string(),
+                                template: match file.https.url(&shortcut, &url) {
+                                    Cow::Owned(upgraded) => Segment::parse(&upgraded),
+                                    Cow::Borrowed(_) => template,
+                                },
+                            }
+                        } else {
+                            warn(format!(
+                                "skipping rule '{pattern}', engine '{shortcut}' not found"
+                            ));
+                            return None;
+                        }
+                    }
+                    (None, Some(url)) => Target::Url {
+                        name: rule.name.unwrap_or(url.clone()),
+                        url,
+                    },
+                    _ => {
+                        warn(format!(
+                            "skipping rule '{pattern}', it needs exactly one of 'engine' or 'url'"
+                        ));
+                        return None;
+                    }
+                };
+
+                Some(Rule { pattern, target })
+            })
+            .collect();
+
+        Self {
             port: file.port,
-            default_engine: force_clone(&default_engine),
+            default_engine,
             broadcast: file.broadcast,
+            search_paths: file.search_paths,
+            query_params: file.query_params,
             engines,
-            path: Some(path),
-        })
+            rules,
+            navigation: file.navigation,
+            https: file.https,
+            stats: file.stats,
+            history: file.history,
+            access: file.access,
+            tls,
+            layers: Vec::new(),
+            files: Vec::new(),
+            variables: Vec::new(),
+            origins: BTreeMap::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// Reads config files and their includes, keeping track of what was read and what went wrong.
+#[derive(Default)]
+struct Loader {
+    /// The chain of files currently being included, to detect cycles.
+    stack: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+    errors: Vec<String>,
+}
+
+impl Loader {
+    fn error(&mut self, message: String) {
+        tracing::error!("{message}");
+        self.errors.push(message);
+    }
+
+    /// Read `path` and everything it includes into a single table.
+    ///
+    /// Included files are merged in the order they're listed (globs in alphabetical order), and
+    /// the including file is merged last, so its settings win.
+    fn load(&mut self, path: &Path) -> Result<toml::Table, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut table: toml::Table = toml::from_str(&text).map_err(|e| e.to_string())?;
+
+        let path = path.canonicalize().unwrap_or(path.to_path_buf());
+        let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+
+        // certificate paths are relative to the file they're written in
+        if let Some(toml::Value::Table(tls)) = table.get_mut("tls") {
+            for key in ["certificate", "key"] {
+                if let Some(toml::Value::String(file)) = tls.get_mut(key) {
+                    *file = dir.join(&*file).to_string_lossy().into_owned();
+                }
+            }
+        }
+
+        let includes = match table.remove("include") {
+            None => Vec::new(),
+            Some(toml::Value::Array(includes)) => includes
+                .into_iter()
+                .map(|include| match include {
+                    toml::Value::String(include) => Ok(include),
+                    _ => Err("'include' must be a list of paths".to_string()),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err("'include' must be a list of paths".into()),
+        };
+
+        if !self.files.contains(&path) {
+            self.files.push(path.clone());
+        }
+        self.stack.push(path);
+
+        let mut merged = toml::Table::new();
+
+        let includes: Vec<PathBuf> = includes
+            .iter()
+            .flat_map(|pattern| self.expand_include(&dir, pattern))
+            .collect();
+
+        for include in includes {
+            let canonical = include.canonicalize().unwrap_or(include.clone());
+
+            if let Some(start) = self.stack.iter().position(|p| *p == canonical) {
+                let cycle = self.stack[start..]
+                    .iter()
+                    .chain([&canonical])
+                    .map(|p| format!("{p:?}"))
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                self.error(format!("skipped include that forms a cycle: {cycle}"));
+                continue;
+            }
+
+            match self.load(&include) {
+                Ok(included) => merge_tables(&mut merged, included),
+                Err(err) => self.error(format!("skipped included config file {include:?}: {err}")),
+            }
+        }
+
+        self.stack.pop();
+        merge_tables(&mut merged, table);
+
+        Ok(merged)
+    }
+
+    /// Resolve an `include` entry relative to `dir`, expanding it if it's a glob.
+    fn expand_include(&mut self, dir: &Path, pattern: &str) -> Vec<PathBuf> {
+        let path = dir.join(pattern);
+
+        if !pattern.contains(['*', '?', '[']) {
+            return vec![path];
+        }
+
+        match glob::glob(&path.to_string_lossy()) {
+            Ok(paths) => paths
+                .filter_map(Result::ok)
+                .filter(|p| p.is_file())
+                .collect(),
+            Err(err) => {
+                self.error(format!(
+                    "skipped invalid include pattern '{pattern}': {err}"
+                ));
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Settings that can be overridden with `LSS_<SETTING>`.
+const VARIABLE_SETTINGS: &[&str] = &[
+    "port",
+    "default",
+    "broadcast",
+    "search_paths",
+    "query_params",
+];
+
+/// Sections whose settings can be overridden with `LSS_<SECTION>_<SETTING>`.
+const VARIABLE_SECTIONS: &[&str] = &["navigation", "https", "stats", "history", "access", "tls"];
+
+/// Collect `LSS_*` environment variables into a config table, returning which variable set each
+/// setting. Values are read as TOML (`9400`, `true`, `["/", "/search"]`), or as a string if that
+/// doesn't fit the setting.
+fn variables_table(loader: &mut Loader) -> (toml::Table, Vec<(String, String)>) {
+    let mut table = toml::Table::new();
+    let mut applied = Vec::new();
+
+    let mut variables: Vec<(String, String)> = env::vars()
+        .filter(|(name, _)| name.starts_with("LSS_"))
+        .collect();
+    variables.sort();
+
+    for (name, value) in variables {
+        let rest = &name["LSS_".len()..];
+
+        let (section, setting) = if let Some(engine) = rest.strip_prefix("ENGINE_") {
+            (Some("engines".to_string()), engine.to_lowercase())
+        } else {
+            let setting = rest.to_lowercase();
+
+            if VARIABLE_SETTINGS.contains(&setting.as_str()) {
+                (None, setting)
+            } else if let Some((section, key)) = setting
+                .split_once('_')
+                .filter(|(section, _)| VARIABLE_SECTIONS.contains(section))
+            {
+                (Some(section.to_string()), key.to_string())
+            } else {
+                // not a setting, like `LSS_CONFIG` or `LSS_DATABASE_PATH`
+                continue;
+            }
+        };
+
+        let entry = |value: toml::Value| {
+            let mut entry = toml::Table::new();
+            match &section {
+                Some(section) => {
+                    let mut inner = toml::Table::new();
+                    inner.insert(setting.clone(), value);
+                    entry.insert(section.clone(), toml::Value::Table(inner));
+                }
+                None => {
+                    entry.insert(setting.clone(), value);
+                }
+            }
+            entry
+        };
+
+        let parsed = toml::from_str::<toml::Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut table| table.remove("value"));
+
+        let valid = |entry: &toml::Table| entry.clone().try_into::<ConfigFile>().map(|_| ());
+
+        let result = match parsed.map(entry) {
+            Some(parsed) if valid(&parsed).is_ok() => Ok(parsed),
+            _ => {
+                let string = entry(toml::Value::String(value));
+                valid(&string).map(|_| string)
+            }
+        };
+
+        match result {
+            Ok(entry) => {
+                merge_tables(&mut table, entry);
+                let setting = match &section {
+                    Some(section) => format!("{section}.{setting}"),
+                    None => setting,
+                };
+                applied.push((name, setting));
+            }
+            Err(err) => loader.error(format!("ignored {name}: {err}")),
+        }
+    }
+
+    (table, applied)
+}
+
+/// Note which layer set each setting in `table`, following the rules of [`merge_tables`].
+fn record_origins(origins: &mut BTreeMap<String, Vec<Layer>>, table: &toml::Table, layer: Layer) {
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(section) => {
+                for name in section.keys() {
+                    origins.insert(format!("{key}.{name}"), vec![layer]);
+                }
+            }
+            toml::Value::Array(list) if is_section_list(list) => {
+                origins.entry(key.clone()).or_default().push(layer);
+            }
+            _ => {
+                origins.insert(key.clone(), vec![layer]);
+            }
+        }
+    }
+}
+
+fn is_section_list(list: &[toml::Value]) -> bool {
+    !list.is_empty() && list.iter().all(toml::Value::is_table)
+}
+
+/// Merge `overlay` into `base`: sections like `[engines]` are merged key by key, lists of
+/// sections like `[[rules]]` are combined with `overlay`'s first, and anything else is replaced.
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(section)), toml::Value::Table(overlay)) => {
+                section.extend(overlay);
+            }
+            (Some(toml::Value::Array(list)), toml::Value::Array(mut overlay))
+                if is_section_list(list) && is_section_list(&overlay) =>
+            {
+                overlay.append(list);
+                *list = overlay;
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
     }
 }
 
@@ -127,13 +700,205 @@ struct ConfigFile {
     default: String,
     #[serde(default)]
     broadcast: bool,
+    #[serde(default = "default::search_paths")]
+    search_paths: Vec<String>,
+    #[serde(default = "default::query_params")]
+    query_params: Vec<String>,
+    #[serde(default)]
+    engines: HashMap<String, EngineFile>,
+    #[serde(default)]
+    rules: Vec<RuleFile>,
+    #[serde(default)]
+    navigation: NavigationConfig,
+    #[serde(default)]
+    https: HttpsConfig,
+    #[serde(default)]
+    stats: StatsConfig,
+    #[serde(default)]
+    history: HistoryConfig,
+    #[serde(default)]
+    access: AccessConfig,
+    #[serde(default)]
+    tls: TlsConfig,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum EngineFile {
+    /// A URL template, `{s}` is replaced with the search terms.
+    Url(String),
+    /// Shortcuts of engines to search with all at once.
+    Group(Vec<String>),
+    /// Search the default engine for `site:<domain>` and the search terms.
+    Site { site: String },
+    /// `false` removes an engine defined in a lower layer or an included file.
+    Enabled(bool),
+}
+
+/// An engine URL template searching `template`'s engine within `site`.
+pub fn site_url(template: &str, site: &str) -> String {
+    template.replace(
+        "{s}",
+        &format!("{}{{s}}", crate::terms::encode(&format!("site:{site} "))),
+    )
+}
+
+/// Checked in order against queries without a shortcut, before the default engine.
+#[derive(serde::Deserialize)]
+struct RuleFile {
+    /// Regular expression tested against the query.
+    pattern: String,
+    /// Shortcut of the engine to search with.
+    engine: Option<String>,
+    /// Or a URL template to redirect to.
+    url: Option<String>,
+    /// Shown in place of `url` in history and statistics.
+    name: Option<String>,
+}
+
+/// Queries that look like addresses, e.g. `docs.rs/serde`, are opened instead of searched.
+#[derive(Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct NavigationConfig {
+    pub enabled: bool,
+    /// Used for addresses typed without one.
+    pub scheme: String,
+}
+
+impl Default for NavigationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scheme: "https".into(),
+        }
+    }
+}
+
+/// Built-in engines with plain `http://` URLs are sent to `https://` instead.
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct HttpsConfig {
     #[serde(default)]
-    engines: HashMap<String, String>,
+    pub upgrade: bool,
+    /// Shortcuts of built-in engines to leave alone, for sites that don't support HTTPS. Any
+    /// shortcut of an engine covers all of them.
+    #[serde(default)]
+    pub exceptions: Vec<String>,
+    /// URLs of the excepted engines, looked up when first needed.
+    #[serde(skip)]
+    excepted_urls: OnceLock<HashSet<String>>,
+}
+
+impl HttpsConfig {
+    /// The URL to use for the built-in engine `shortcut`, upgraded if enabled and not excepted.
+    pub fn url<'a>(&self, shortcut: &str, url: &'a str) -> Cow<'a, str> {
+        if !self.upgrade || self.excepted(shortcut) {
+            return url.into();
+        }
+
+        crate::bangs::upgrade_https(url).map_or(url.into(), Cow::Owned)
+    }
+
+    /// Whether `shortcut`, or another shortcut of the same engine, is an exception.
+    fn excepted(&self, shortcut: &str) -> bool {
+        let listed = |shortcut: &str| {
+            self.exceptions
+                .iter()
+                .any(|e| e.trim_start_matches('!').eq_ignore_ascii_case(shortcut))
+        };
+
+        // aliases share their engine's URL, even when a source lists them as separate bangs
+        let excepted_urls = self.excepted_urls.get_or_init(|| {
+            self.exceptions
+                .iter()
+                .filter_map(|e| crate::ENGINES.get_engine(e.trim_start_matches('!')))
+                .map(|engine| engine.url.to_string())
+                .collect()
+        });
+
+        listed(shortcut)
+            || crate::ENGINES
+                .get_engine(shortcut)
+                .is_some_and(|engine| excepted_urls.contains(engine.url))
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct StatsConfig {
+    /// Count how often each shortcut is used.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Also remember the search terms used with each shortcut.
+    #[serde(default)]
+    pub queries: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Record every search in the local history file.
+    pub enabled: bool,
+    /// Oldest entries are dropped past this many, `0` keeps everything.
+    pub max_entries: usize,
+    /// Entries older than this are dropped, `0` keeps everything.
+    pub max_age_days: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_entries: 10_000,
+            max_age_days: 90,
+        }
+    }
+}
+
+/// Only applies when `broadcast = true`.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct AccessConfig {
+    /// Client address ranges allowed to connect, everyone if empty. Loopback is always allowed.
+    pub allow: Vec<Cidr>,
+    /// Shared secret accepted as a `token` query parameter.
+    pub token: Option<String>,
+    /// HTTP basic auth credentials.
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// PEM certificate chain, HTTPS is enabled when this and `key` are set.
+    pub certificate: Option<PathBuf>,
+    /// PEM private key.
+    pub key: Option<PathBuf>,
+    /// Port to serve HTTPS on.
+    pub port: u16,
+    /// Keep serving plain HTTP on `port` alongside HTTPS.
+    pub http: bool,
+}
+
+impl TlsConfig {
+    pub fn enabled(&self) -> bool {
+        self.certificate.is_some() && self.key.is_some()
+    }
 }
 
-type OwnedSearchEngine = InternalSearchEngine<CompactString, Option<CompactString>>;
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            certificate: None,
+            key: None,
+            port: default::tls_port(),
+            http: true,
+        }
+    }
+}
 
-fn force_clone(engine: &SearchEngineRef) -> OwnedSearchEngine {
+pub type OwnedSearchEngine = InternalSearchEngine<CompactString, Option<CompactString>>;
+
+pub fn force_clone(engine: &SearchEngineRef) -> OwnedSearchEngine {
     OwnedSearchEngine {
         name: engine.name.to_compact_string(),
         url: engine.url.to_compact_string(),
@@ -141,3 +906,68 @@ fn force_clone(engine: &SearchEngineRef) -> OwnedSearchEngine {
         subcategory: engine.subcategory.map(|s| s.to_compact_string()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn https_exceptions_cover_aliases() {
+        let https = HttpsConfig {
+            upgrade: true,
+            exceptions: vec!["!tfd".into()],
+            ..Default::default()
+        };
+        let tfd = crate::ENGINES.get_engine("tfd").unwrap().url;
+        let gmap = crate::ENGINES.get_engine("gmap").unwrap().url;
+        assert!(tfd.starts_with("http://") && gmap.starts_with("http://"));
+
+        assert_eq!(https.url("tfd", tfd), tfd);
+        assert_eq!(https.url("define", tfd), tfd);
+        assert_eq!(https.url("gmap", gmap), gmap.replacen("http", "https", 1));
+
+        let off = HttpsConfig::default();
+        assert_eq!(off.url("gmap", gmap), gmap);
+    }
+
+    #[test]
+    fn rule_targets() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            engines.wiki = "https://{s}.example.org/"
+            https.upgrade = true
+
+            [[rules]]
+            pattern = "^\\p{L}+$"
+            engine = "wiki"
+
+            [[rules]]
+            pattern = "^\\d+$"
+            engine = "gmap"
+
+            [[rules]]
+            pattern = "^x"
+            engine = "missing"
+            "#,
+        )
+        .unwrap();
+        let mut errors = Vec::new();
+        let config = Config::from_config_file(file, &mut errors);
+
+        assert_eq!(config.rules.len(), 2);
+        assert_eq!(errors, ["skipping rule '^x', engine 'missing' not found"]);
+
+        let (url, name) = config.rules[0].apply("bücher").unwrap();
+        assert_eq!(url, "https://xn--bcher-kva.example.org/");
+        assert_eq!(name, "wiki");
+
+        // built-in engines are upgraded like their shortcuts are
+        let gmap = crate::ENGINES.get_engine("gmap").unwrap();
+        let (url, name) = config.rules[1].apply("42").unwrap();
+        assert_eq!(
+            url,
+            crate::engine_url(gmap.url, "42").replacen("http", "https", 1)
+        );
+        assert_eq!(name, gmap.name);
+    }
+}
@@ -0,0 +1,190 @@
+//! The `dump` subcommand, showing what ended up in the search engine database.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use serde::Serialize;
+
+use crate::{bangs::BuildReport, engines::SearchEngineRef, lint::Lint, CONFIG, ENGINES};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            _ => Err(format!("unknown format '{s}', expected json or toml")),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Dump {
+    build: Build,
+    engines: BTreeMap<String, Engine>,
+    groups: BTreeMap<String, Group>,
+}
+
+#[derive(Debug, Serialize)]
+struct Build {
+    last_updated: &'static str,
+    database_version: u32,
+    sources: Vec<Source>,
+    /// Shortcuts whose URL was upgraded to HTTPS when building.
+    upgraded: Vec<String>,
+    /// Number of lint findings of each kind.
+    lint_counts: BTreeMap<String, usize>,
+    /// Findings at warning level or above.
+    lints: Vec<Lint>,
+}
+
+/// A [`SourceReport`](crate::bangs::SourceReport) with the counts spelled out.
+#[derive(Debug, Serialize)]
+struct Source {
+    source: String,
+    bangs: usize,
+    skipped: usize,
+    rewritten: usize,
+    skipped_shortcuts: Vec<String>,
+    rewritten_shortcuts: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Engine {
+    name: String,
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subcategory: Option<String>,
+    source: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct Group {
+    name: String,
+    members: Vec<String>,
+    source: &'static str,
+}
+
+impl Engine {
+    fn new(engine: SearchEngineRef, source: &'static str) -> Self {
+        Self {
+            name: engine.name.to_string(),
+            url: engine.url.to_string(),
+            category: engine.category.map(str::to_string),
+            subcategory: engine.subcategory.map(str::to_string),
+            source,
+        }
+    }
+}
+
+/// The sources `build.rs` read, what it skipped or rewrote in each, and what its checks found.
+fn build_report() -> BuildReport {
+    serde_json::from_str(include_str!(env!("LSS_BUILD_REPORT")))
+        .expect("build report written by build.rs is invalid")
+}
+
+/// Shortcuts whose URL `build.rs` upgraded to HTTPS.
+pub fn upgraded() -> impl Iterator<Item = &'static str> {
+    include_str!(env!("LSS_UPGRADED")).lines()
+}
+
+/// Every built-in engine and group, plus custom ones when `custom` is set, and the number of
+/// shortcuts left out because another one takes precedence.
+fn collect(custom: bool) -> (Dump, usize) {
+    let report = build_report();
+
+    let mut dump = Dump {
+        build: Build {
+            last_updated: include_str!(env!("LSS_LAST_UPDATED")),
+            database_version: crate::engines::DATABASE_VERSION,
+            sources: report
+                .sources
+                .into_iter()
+                .map(|report| Source {
+                    source: report.source,
+                    bangs: report.bangs,
+                    skipped: report.skipped.len(),
+                    rewritten: report.rewritten.len(),
+                    skipped_shortcuts: report.skipped,
+                    rewritten_shortcuts: report.rewritten,
+                })
+                .collect(),
+            upgraded: upgraded().map(str::to_string).collect(),
+            lint_counts: report.lint_counts,
+            lints: report.lints,
+        },
+        engines: BTreeMap::new(),
+        groups: BTreeMap::new(),
+    };
+
+    for shortcut in ENGINES.shortcuts() {
+        if let Some(engine) = ENGINES.get_engine(shortcut) {
+            dump.engines
+                .insert(shortcut.to_string(), Engine::new(engine, "builtin"));
+        } else if let Some(group) = ENGINES.get_group(shortcut) {
+            dump.groups.insert(
+                shortcut.to_string(),
+                Group {
+                    name: group.name.to_string(),
+                    members: group.members.iter().map(|m| m.to_string()).collect(),
+                    source: "builtin",
+                },
+            );
+        }
+    }
+
+    let mut hidden = 0;
+
+    if custom {
+        for shortcut in CONFIG.engines.shortcuts() {
+            // any engine takes precedence over groups, just like when searching
+            if let Some(engine) = CONFIG.engines.get_engine(shortcut) {
+                if ENGINES.get_engine(shortcut).is_some() {
+                    hidden += 1;
+                    continue;
+                }
+                if dump.groups.remove(shortcut).is_some() {
+                    hidden += 1;
+                }
+                dump.engines
+                    .insert(shortcut.to_string(), Engine::new(engine, "custom"));
+            } else if let Some(group) = CONFIG.engines.get_group(shortcut) {
+                if dump.engines.contains_key(shortcut) || dump.groups.contains_key(shortcut) {
+                    hidden += 1;
+                    continue;
+                }
+                dump.groups.insert(
+                    shortcut.to_string(),
+                    Group {
+                        name: group.name.to_string(),
+                        members: group.members.iter().map(|m| m.to_string()).collect(),
+                        source: "custom",
+                    },
+                );
+            }
+        }
+    }
+
+    (dump, hidden)
+}
+
+/// Serialize the database, and custom engines if `custom` is set, returning the text and the
+/// number of shortcuts left out.
+pub fn dump(format: Format, custom: bool) -> Result<(String, usize), String> {
+    let (dump, hidden) = collect(custom);
+
+    let text = match format {
+        Format::Json => serde_json::to_string_pretty(&dump).map_err(|e| e.to_string())? + "\n",
+        Format::Toml => toml::to_string_pretty(&dump).map_err(|e| e.to_string())?,
+    };
+
+    Ok((text, hidden))
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use compact_str::CompactString;
 use indexmap::IndexSet;
@@ -6,6 +6,15 @@ use rkyv::{rend::u32_le, string::ArchivedString, Archive, Deserialize, Serialize
 
 type StringIndex = usize;
 
+/// Written by `build.rs` at the start of every serialized database.
+pub const DATABASE_MAGIC: [u8; 8] = *b"LSSENGDB";
+
+/// Bump whenever the archived layout of [`SearchEngineDatabase`] changes.
+pub const DATABASE_VERSION: u32 = 3;
+
+/// Magic and version, padded to 16 bytes so the archive after it stays aligned.
+const HEADER_LEN: usize = 16;
+
 pub type SearchEngineRef<'a> = InternalSearchEngine<&'a str, Option<&'a str>>;
 type DiskSearchEngine = InternalSearchEngine<CompactString, StringIndex>;
 
@@ -17,11 +26,114 @@ pub struct InternalSearchEngine<S, C> {
     pub subcategory: C,
 }
 
+/// A piece of an engine's URL template.
+#[derive(Debug, Clone, Archive, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Segment {
+    Literal(CompactString),
+    /// The search terms, `{s}` in config files and `{{{s}}}` in DuckDuckGo's bangs.
+    Terms,
+    /// DuckDuckGo's `{inputEncoding}`, always `UTF-8` since that's how the terms are encoded.
+    InputEncoding,
+}
+
+/// Where in a URL the search terms go, which decides how they have to be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// Part of the host, like `{s}.wikipedia.org`.
+    Host,
+    /// Part of the path, like `/wiki/{s}`.
+    Path,
+    /// In the query string or fragment.
+    Query,
+}
+
+impl Position {
+    /// The position of whatever follows `prefix`, the start of a URL.
+    pub fn after(prefix: &str) -> Self {
+        if prefix.contains(['?', '#']) {
+            return Self::Query;
+        }
+
+        match prefix.split_once("://") {
+            Some((_, authority)) if !authority.contains('/') => Self::Host,
+            _ => Self::Path,
+        }
+    }
+}
+
+impl Segment {
+    /// Parse a URL template where `{s}` is the only placeholder.
+    pub fn parse(url: &str) -> Vec<Segment> {
+        let mut template = Vec::new();
+
+        for (idx, literal) in url.split("{s}").enumerate() {
+            if idx > 0 {
+                template.push(Segment::Terms);
+            }
+            if !literal.is_empty() {
+                template.push(Segment::Literal(literal.into()));
+            }
+        }
+
+        template
+    }
+
+    /// Fill in `template` with the search terms, as `encode` writes them for each position.
+    pub fn render(template: &[Segment], encode: impl Fn(Position) -> String) -> String {
+        let mut url = String::new();
+
+        for segment in template {
+            match segment {
+                Segment::Literal(literal) => url.push_str(literal),
+                Segment::Terms => {
+                    let terms = encode(Position::after(&url));
+                    url.push_str(&terms);
+                }
+                Segment::InputEncoding => url.push_str("UTF-8"),
+            }
+        }
+
+        url
+    }
+
+    /// Write `template` back out with `{s}` for the search terms, other placeholders filled in.
+    pub fn to_url(template: &[Segment]) -> String {
+        Self::render(template, |_| "{s}".to_string())
+    }
+}
+
+impl From<&ArchivedSegment> for Segment {
+    fn from(segment: &ArchivedSegment) -> Self {
+        match segment {
+            ArchivedSegment::Literal(literal) => Segment::Literal(literal.as_str().into()),
+            ArchivedSegment::Terms => Segment::Terms,
+            ArchivedSegment::InputEncoding => Segment::InputEncoding,
+        }
+    }
+}
+
+/// A shortcut that searches with several other shortcuts at once.
+#[derive(Debug, Archive, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SearchGroup {
+    pub name: CompactString,
+    /// Shortcuts of the member engines, without the leading `!`.
+    pub members: Vec<CompactString>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchGroupRef<'a> {
+    pub name: &'a str,
+    pub members: Vec<&'a str>,
+}
+
 #[derive(Debug, Archive, Serialize, Deserialize)]
 pub struct SearchEngineDatabase {
     shortcuts: HashMap<CompactString, usize>,
     engines: IndexSet<DiskSearchEngine>,
     categories: IndexSet<CompactString>,
+    groups: HashMap<CompactString, SearchGroup>,
+    /// URL templates, by the index of their engine.
+    templates: Vec<Vec<Segment>>,
 }
 
 impl Default for SearchEngineDatabase {
@@ -30,6 +142,8 @@ impl Default for SearchEngineDatabase {
             shortcuts: HashMap::new(),
             engines: IndexSet::new(),
             categories: IndexSet::new(),
+            groups: HashMap::new(),
+            templates: Vec::new(),
         };
 
         me.categories.insert(CompactString::default());
@@ -59,6 +173,17 @@ impl SearchEngineDatabase {
         &mut self,
         shortcut: &CompactString,
         engine: InternalSearchEngine<CompactString, Option<CompactString>>,
+    ) {
+        let template = Segment::parse(&engine.url);
+        self.insert_template(shortcut, engine, template);
+    }
+
+    /// Insert an engine whose URL template was already parsed, `engine.url` is only shown.
+    pub fn insert_template(
+        &mut self,
+        shortcut: &CompactString,
+        engine: InternalSearchEngine<CompactString, Option<CompactString>>,
+        template: Vec<Segment>,
     ) {
         let disk = DiskSearchEngine {
             name: engine.name,
@@ -68,9 +193,19 @@ impl SearchEngineDatabase {
         };
 
         let (idx, _) = self.engines.insert_full(disk);
+        if idx == self.templates.len() {
+            self.templates.push(template);
+        } else {
+            self.templates[idx] = template;
+        }
+
         self.shortcuts.insert(shortcut.to_lowercase(), idx);
     }
 
+    pub fn insert_group(&mut self, shortcut: &CompactString, group: SearchGroup) {
+        self.groups.insert(shortcut.to_lowercase(), group);
+    }
+
     fn insert_category(&mut self, s: Option<CompactString>) -> StringIndex {
         if let Some(s) = s {
             self.categories.insert_full(s).0
@@ -86,6 +221,13 @@ impl SearchEngineDatabase {
             .map(|disk| self.construct_engine(disk))
     }
 
+    pub fn get_template(&self, shortcut: &str) -> Option<Vec<Segment>> {
+        self.shortcuts
+            .get(shortcut.to_lowercase().as_str())
+            .and_then(|idx| self.templates.get(*idx))
+            .cloned()
+    }
+
     fn construct_engine<'a>(&'a self, disk: &'a DiskSearchEngine) -> SearchEngineRef<'a> {
         SearchEngineRef {
             name: disk.name.as_str(),
@@ -107,6 +249,41 @@ impl SearchEngineDatabase {
         self.engines.len()
     }
 
+    pub fn get_group(&self, shortcut: &str) -> Option<SearchGroupRef<'_>> {
+        self.groups
+            .get(shortcut.to_lowercase().as_str())
+            .map(|group| SearchGroupRef {
+                name: group.name.as_str(),
+                members: group.members.iter().map(CompactString::as_str).collect(),
+            })
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&str, SearchGroupRef<'_>)> {
+        self.groups.keys().filter_map(|shortcut| {
+            self.get_group(shortcut)
+                .map(|group| (shortcut.as_str(), group))
+        })
+    }
+
+    /// Shortcuts of every engine and group.
+    pub fn shortcuts(&self) -> impl Iterator<Item = &str> {
+        self.shortcuts
+            .keys()
+            .chain(self.groups.keys())
+            .map(CompactString::as_str)
+    }
+
+    /// Serialize the database behind a [`DATABASE_MAGIC`] and [`DATABASE_VERSION`] header.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, rkyv::rancor::Error> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(&DATABASE_MAGIC);
+        bytes.extend_from_slice(&DATABASE_VERSION.to_le_bytes());
+        bytes.resize(HEADER_LEN, 0);
+        bytes.extend_from_slice(&rkyv::to_bytes::<rkyv::rancor::Error>(self)?);
+
+        Ok(bytes)
+    }
+
     pub fn engines(&self) -> impl Iterator<Item = (Vec<&str>, SearchEngineRef<'_>)> {
         self.engines.iter().enumerate().map(|(idx, disk)| {
             (
@@ -122,6 +299,29 @@ impl SearchEngineDatabase {
 }
 
 impl ArchivedSearchEngineDatabase {
+    /// Check the header written by [`SearchEngineDatabase::to_bytes`] and validate the archive.
+    ///
+    /// `bytes` must be 16-byte aligned.
+    pub fn from_bytes(bytes: &[u8]) -> Result<&Self, DatabaseError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DatabaseError::Truncated);
+        }
+
+        let (header, archive) = bytes.split_at(HEADER_LEN);
+
+        if header[..DATABASE_MAGIC.len()] != DATABASE_MAGIC {
+            return Err(DatabaseError::NotADatabase);
+        }
+
+        // unwrap: slice is exactly four bytes long
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != DATABASE_VERSION {
+            return Err(DatabaseError::IncompatibleVersion(version));
+        }
+
+        rkyv::access::<Self, rkyv::rancor::Error>(archive).map_err(DatabaseError::Invalid)
+    }
+
     pub fn get_engine<'a>(&'a self, shortcut: &str) -> Option<SearchEngineRef<'a>> {
         self.shortcuts
             .get(shortcut.to_lowercase().as_str())
@@ -129,6 +329,13 @@ impl ArchivedSearchEngineDatabase {
             .map(|disk| self.construct_engine(disk))
     }
 
+    pub fn get_template(&self, shortcut: &str) -> Option<Vec<Segment>> {
+        self.shortcuts
+            .get(shortcut.to_lowercase().as_str())
+            .and_then(|idx| self.templates.get((*idx).to_native() as usize))
+            .map(|template| template.iter().map(Segment::from).collect())
+    }
+
     fn construct_engine<'a>(
         &'a self,
         disk: &'a ArchivedInternalSearchEngine<compact_str::CompactString, usize>,
@@ -155,6 +362,30 @@ impl ArchivedSearchEngineDatabase {
         self.engines.len()
     }
 
+    pub fn get_group(&self, shortcut: &str) -> Option<SearchGroupRef<'_>> {
+        self.groups
+            .get(shortcut.to_lowercase().as_str())
+            .map(|group| SearchGroupRef {
+                name: group.name.as_str(),
+                members: group.members.iter().map(ArchivedString::as_str).collect(),
+            })
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = (&str, SearchGroupRef<'_>)> {
+        self.groups.keys().filter_map(|shortcut| {
+            self.get_group(shortcut)
+                .map(|group| (shortcut.as_str(), group))
+        })
+    }
+
+    /// Shortcuts of every engine and group.
+    pub fn shortcuts(&self) -> impl Iterator<Item = &str> {
+        self.shortcuts
+            .keys()
+            .chain(self.groups.keys())
+            .map(ArchivedString::as_str)
+    }
+
     pub fn engines(&self) -> impl Iterator<Item = (Vec<&str>, SearchEngineRef<'_>)> {
         self.engines.iter().enumerate().map(|(idx, disk)| {
             (
@@ -169,6 +400,28 @@ impl ArchivedSearchEngineDatabase {
     }
 }
 
+#[derive(Debug)]
+pub enum DatabaseError {
+    Truncated,
+    NotADatabase,
+    IncompatibleVersion(u32),
+    Invalid(rkyv::rancor::Error),
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "database file is truncated"),
+            Self::NotADatabase => write!(f, "not a local-search-shortcuts database"),
+            Self::IncompatibleVersion(version) => write!(
+                f,
+                "database format version {version} is incompatible with this build (expected {DATABASE_VERSION})"
+            ),
+            Self::Invalid(err) => write!(f, "database failed validation: {err}"),
+        }
+    }
+}
+
 #[allow(dead_code)]
 pub mod default {
     pub fn engine() -> String {
@@ -178,4 +431,90 @@ pub mod default {
     pub fn port() -> u16 {
         9322
     }
+
+    pub fn tls_port() -> u16 {
+        9443
+    }
+
+    pub fn search_paths() -> Vec<String> {
+        vec!["/".to_string()]
+    }
+
+    pub fn query_params() -> Vec<String> {
+        vec!["q".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(text: &str) -> Segment {
+        Segment::Literal(text.into())
+    }
+
+    #[test]
+    fn parse() {
+        let cases: &[(&str, &[Segment])] = &[
+            ("https://example.org/", &[literal("https://example.org/")]),
+            (
+                "https://example.org/?q={s}",
+                &[literal("https://example.org/?q="), Segment::Terms],
+            ),
+            (
+                "https://{s}.example.org/{s}",
+                &[
+                    literal("https://"),
+                    Segment::Terms,
+                    literal(".example.org/"),
+                    Segment::Terms,
+                ],
+            ),
+            ("{s}", &[Segment::Terms]),
+        ];
+
+        for (url, template) in cases {
+            assert_eq!(Segment::parse(url), *template, "{url}");
+            assert_eq!(Segment::to_url(template), *url);
+        }
+    }
+
+    #[test]
+    fn positions() {
+        let cases = [
+            ("https://", Position::Host),
+            ("https://en.", Position::Host),
+            ("https://example.org/", Position::Path),
+            ("https://example.org/wiki/", Position::Path),
+            ("https://example.org/?q=", Position::Query),
+            ("https://example.org/#", Position::Query),
+            ("https://example.org/?r=a/b&q=", Position::Query),
+            ("", Position::Path),
+        ];
+
+        for (prefix, position) in cases {
+            assert_eq!(Position::after(prefix), position, "{prefix}");
+        }
+    }
+
+    #[test]
+    fn render() {
+        let template = [
+            literal("https://"),
+            Segment::Terms,
+            literal(".example.org/"),
+            Segment::Terms,
+            literal("?ie="),
+            Segment::InputEncoding,
+            literal("&q="),
+            Segment::Terms,
+        ];
+        let url = Segment::render(&template, |position| format!("{position:?}"));
+        assert_eq!(url, "https://Host.example.org/Path?ie=UTF-8&q=Query");
+
+        assert_eq!(
+            Segment::to_url(&template),
+            "https://{s}.example.org/{s}?ie=UTF-8&q={s}"
+        );
+    }
 }
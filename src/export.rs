@@ -0,0 +1,233 @@
+//! The `export` subcommand, writing engines in formats browsers can use without the service.
+
+use std::{collections::HashSet, fmt::Write, fs, path::Path, str::FromStr};
+
+use regex::Regex;
+
+use crate::{info::escape_html, Source, CONFIG, ENGINES};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Netscape bookmarks HTML with keywords, importable by Firefox.
+    Bookmarks,
+    /// A JSON list of Chromium site search entries.
+    Chromium,
+    /// One OpenSearch description per shortcut.
+    OpenSearch,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bookmarks" => Ok(Self::Bookmarks),
+            "chromium" => Ok(Self::Chromium),
+            "opensearch" => Ok(Self::OpenSearch),
+            _ => Err(format!(
+                "unknown format '{s}', expected bookmarks, chromium or opensearch"
+            )),
+        }
+    }
+}
+
+/// Which engines to export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subset {
+    Custom,
+    Builtin,
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub shortcut: String,
+    pub name: String,
+    /// URL template with `{s}` in place of the search terms.
+    pub url: String,
+}
+
+/// Every shortcut in `subset` whose shortcut or name matches `filter`, sorted by shortcut, and
+/// the number of groups left out. Links to this service's own pages are left out too, they
+/// don't work without it.
+pub fn entries(subset: Subset, filter: Option<&Regex>) -> (Vec<Entry>, usize) {
+    let builtin = matches!(subset, Subset::Builtin | Subset::All);
+    let custom = matches!(subset, Subset::Custom | Subset::All);
+
+    let shortcuts = builtin
+        .then(|| {
+            ENGINES
+                .shortcuts()
+                .map(|s| (s, ENGINES.get_engine(s), Source::Builtin))
+        })
+        .into_iter()
+        .flatten()
+        .chain(
+            custom
+                .then(|| {
+                    CONFIG
+                        .engines
+                        .shortcuts()
+                        .map(|s| (s, CONFIG.engines.get_engine(s), Source::Custom))
+                })
+                .into_iter()
+                .flatten(),
+        );
+
+    let mut entries = Vec::new();
+    let mut groups = 0;
+
+    for (shortcut, engine, source) in shortcuts {
+        let Some(engine) = engine else {
+            groups += 1;
+            continue;
+        };
+
+        if engine.url.starts_with('/') {
+            continue;
+        }
+
+        if filter.is_some_and(|f| !f.is_match(shortcut) && !f.is_match(engine.name)) {
+            continue;
+        }
+
+        entries.push(Entry {
+            shortcut: shortcut.to_string(),
+            name: engine.name.to_string(),
+            url: crate::search_template(shortcut, &engine, source),
+        });
+    }
+
+    // stable, so built-ins stay ahead of custom engines and win like they do in searches
+    entries.sort_by(|a, b| a.shortcut.cmp(&b.shortcut));
+    entries.dedup_by(|later, earlier| later.shortcut == earlier.shortcut);
+
+    (entries, groups)
+}
+
+pub fn bookmarks(entries: &[Entry]) -> String {
+    let mut output = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+        <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+        <TITLE>Bookmarks</TITLE>\n\
+        <H1>Bookmarks</H1>\n\
+        <DL><p>\n\
+        <DT><H3>Local Search Shortcuts</H3>\n\
+        <DL><p>\n",
+    );
+
+    for entry in entries {
+        let _ = writeln!(
+            output,
+            "<DT><A HREF=\"{}\" SHORTCUTURL=\"{}\">{}</A>",
+            escape_html(&entry.url.replace("{s}", "%s")),
+            escape_html(&entry.shortcut),
+            escape_html(&entry.name)
+        );
+    }
+
+    output.push_str("</DL><p>\n</DL><p>\n");
+    output
+}
+
+pub fn chromium(entries: &[Entry]) -> String {
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            serde_json::json!({
+                "name": entry.name,
+                "keyword": entry.shortcut,
+                "url": entry.url.replace("{s}", "%s"),
+            })
+        })
+        .collect();
+
+    // unwrap: plain JSON values always serialize
+    serde_json::to_string_pretty(&entries).unwrap() + "\n"
+}
+
+pub fn opensearch(entry: &Entry) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+  <ShortName>{}</ShortName>
+  <Description>{} (!{})</Description>
+  <InputEncoding>UTF-8</InputEncoding>
+  <Url type="text/html" method="get" template="{}"/>
+</OpenSearchDescription>
+"#,
+        escape_html(&entry.name.chars().take(16).collect::<String>()),
+        escape_html(&entry.name),
+        escape_html(&entry.shortcut),
+        escape_html(&entry.url.replace("{s}", "{searchTerms}"))
+    )
+}
+
+/// Write one OpenSearch file per entry into `dir`, returning how many were written.
+///
+/// Engines without search terms are skipped, OpenSearch requires them. Shortcuts that end up with
+/// the same file name, like `a.b` and `a_b`, get a numbered suffix.
+pub fn write_opensearch(entries: &[Entry], dir: &Path) -> Result<usize, String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let mut written = 0;
+    // lowercased, for case-insensitive file systems
+    let mut taken = HashSet::new();
+
+    for entry in entries.iter().filter(|e| e.url.contains("{s}")) {
+        let file: String = entry
+            .shortcut
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        let mut name = file.clone();
+        let mut suffix = 1;
+        while !taken.insert(name.to_lowercase()) {
+            suffix += 1;
+            name = format!("{file}-{suffix}");
+        }
+
+        fs::write(dir.join(format!("{name}.xml")), opensearch(entry)).map_err(|e| e.to_string())?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opensearch_file_names() {
+        let entry = |shortcut: &str| Entry {
+            shortcut: shortcut.to_string(),
+            name: shortcut.to_string(),
+            url: format!("https://example.org/{shortcut}?q={{s}}"),
+        };
+        let entries = ["a.b", "a_b", "A_b", "a_b-2", "c"].map(entry);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(write_opensearch(&entries, dir.path()), Ok(5));
+
+        let mut files: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|file| file.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            ["A_b-3.xml", "a_b-2-2.xml", "a_b-2.xml", "a_b.xml", "c.xml"]
+        );
+
+        let a_b = fs::read_to_string(dir.path().join("a_b-2.xml")).unwrap();
+        assert!(a_b.contains("(!a_b)"));
+    }
+}
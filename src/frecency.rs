@@ -0,0 +1,127 @@
+//! Ranks shortcuts by how often *and* how recently they were used, based on [`crate::stats`].
+//!
+//! Every use counts for less as it ages, halving every [`HALF_LIFE`], so a shortcut used a lot
+//! months ago falls behind one used a few times this week. Only the decayed sum is stored, which
+//! is updated on each use.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::stats::{now, shortcut_usage};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// How long it takes for a use to count half as much.
+pub const HALF_LIFE: u64 = 30 * DAY;
+
+/// `score`, as of `since`, with its uses aged to `now`.
+pub fn decay(score: f64, since: u64, now: u64) -> f64 {
+    score * 0.5f64.powf(now.saturating_sub(since) as f64 / HALF_LIFE as f64)
+}
+
+/// `score`, as of `since`, with another use at `now`.
+pub fn add_use(score: f64, since: u64, now: u64) -> f64 {
+    decay(score, since, now) + 1.0
+}
+
+pub fn scores() -> HashMap<String, f64> {
+    let now = now();
+
+    shortcut_usage()
+        .into_iter()
+        .map(|(shortcut, _, last_used, score)| (shortcut, decay(score, last_used, now)))
+        .collect()
+}
+
+/// Used shortcuts with their use count, highest scoring first.
+pub fn ranked() -> Vec<(String, u64)> {
+    rank(shortcut_usage(), now())
+}
+
+/// Order `(shortcut, count, last_used, score)` by score at `now`, then by shortcut.
+fn rank(usage: Vec<(String, u64, u64, f64)>, now: u64) -> Vec<(String, u64)> {
+    let mut ranked: Vec<(String, u64, f64)> = usage
+        .into_iter()
+        .map(|(shortcut, count, last_used, score)| (shortcut, count, decay(score, last_used, now)))
+        .collect();
+
+    ranked.sort_by(|(a, _, a_score), (b, _, b_score)| {
+        b_score.total_cmp(a_score).then_with(|| a.cmp(b))
+    });
+
+    ranked
+        .into_iter()
+        .map(|(shortcut, count, _)| (shortcut, count))
+        .collect()
+}
+
+/// Compare shortcuts by their scores in `scores`, highest first, counting unused ones as zero.
+pub fn compare(scores: &HashMap<String, f64>, a: &str, b: &str) -> Ordering {
+    let score = |shortcut| scores.get(shortcut).copied().unwrap_or_default();
+    score(b).total_cmp(&score(a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_000 * DAY;
+
+    /// The score of uses at each of `ages`, in days, oldest first.
+    fn uses(ages: &[u64]) -> (f64, u64) {
+        ages.iter().fold((0.0, 0), |(score, since), age| {
+            let at = NOW - age * DAY;
+            (add_use(score, since, at), at)
+        })
+    }
+
+    #[test]
+    fn decay_halves() {
+        assert_eq!(decay(8.0, NOW, NOW), 8.0);
+        assert_eq!(decay(8.0, NOW - HALF_LIFE, NOW), 4.0);
+        assert_eq!(decay(8.0, NOW - 3 * HALF_LIFE, NOW), 1.0);
+        // a clock going backwards doesn't make uses count more
+        assert_eq!(decay(8.0, NOW + DAY, NOW), 8.0);
+    }
+
+    #[test]
+    fn each_use_decays() {
+        let (score, since) = uses(&[60, 60, 30, 0]);
+        assert!((decay(score, since, NOW) - 2.0).abs() < 1e-9);
+
+        // the same count and last use, but older uses count for less
+        let (recent, recent_since) = uses(&[1, 1, 0]);
+        let (old, old_since) = uses(&[300, 300, 0]);
+        assert_eq!(recent_since, old_since);
+        assert!(decay(recent, recent_since, NOW) > decay(old, old_since, NOW));
+    }
+
+    #[test]
+    fn ordering() {
+        let entry = |shortcut: &str, ages: &[u64]| {
+            let (score, since) = uses(ages);
+            (shortcut.to_string(), ages.len() as u64, since, score)
+        };
+
+        let ranked = rank(
+            vec![
+                entry("old", &[200; 10]),
+                entry("often", &[5; 10]),
+                entry("recent", &[0, 0]),
+                entry("b", &[0]),
+                entry("a", &[0]),
+            ],
+            NOW,
+        );
+
+        assert_eq!(
+            ranked,
+            [
+                ("often".to_string(), 10),
+                ("recent".to_string(), 2),
+                ("a".to_string(), 1),
+                ("b".to_string(), 1),
+                ("old".to_string(), 10),
+            ]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex, PoisonError},
+};
+
+use crate::{
+    info::{base_html, escape_html, format_timestamp},
+    stats::now,
+    Redirect, CONFIG,
+};
+
+pub static HISTORY_PATH: LazyLock<Option<PathBuf>> =
+    LazyLock::new(|| dirs::data_dir().map(|dir| dir.join("local-search-shortcuts/history.jsonl")));
+
+static HISTORY: LazyLock<Mutex<Vec<Entry>>> = LazyLock::new(|| Mutex::new(load()));
+
+/// The most entries shown on `/history` at once, exports are never truncated.
+const MAX_DISPLAYED: usize = 500;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub time: u64,
+    pub shortcut: Option<String>,
+    pub engine: String,
+    pub query: String,
+}
+
+pub fn record(redirect: &Redirect) {
+    if !CONFIG.history.enabled || redirect.private {
+        return;
+    }
+
+    let entry = Entry {
+        time: now(),
+        shortcut: redirect.shortcut.clone(),
+        engine: redirect.engine.clone(),
+        query: redirect.terms.clone(),
+    };
+
+    let mut history = HISTORY.lock().unwrap_or_else(PoisonError::into_inner);
+    history.push(entry.clone());
+
+    let result = if prune(&mut history) {
+        rewrite(&history)
+    } else {
+        append(&entry)
+    };
+
+    if let Err(err) = result {
+        tracing::warn!("failed to save search history: {err}");
+    }
+}
+
+pub fn clear() {
+    let mut history = HISTORY.lock().unwrap_or_else(PoisonError::into_inner);
+    history.clear();
+
+    if let Some(path) = HISTORY_PATH.as_ref().filter(|path| path.exists()) {
+        if let Err(err) = fs::remove_file(path) {
+            tracing::warn!("failed to remove search history {path:?}: {err}");
+        }
+    }
+}
+
+fn load() -> Vec<Entry> {
+    let Some(path) = HISTORY_PATH.as_ref().filter(|path| path.exists()) else {
+        return Vec::new();
+    };
+
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            tracing::warn!("failed to read search history {path:?}: {err}");
+            return Vec::new();
+        }
+    };
+
+    let mut history: Vec<Entry> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(line)
+                .inspect_err(|err| tracing::warn!("skipping malformed history entry: {err}"))
+                .ok()
+        })
+        .collect();
+
+    if prune(&mut history) {
+        if let Err(err) = rewrite(&history) {
+            tracing::warn!("failed to save search history: {err}");
+        }
+    }
+
+    history
+}
+
+/// Apply `history.max_entries` and `history.max_age_days`, returning whether anything was removed.
+fn prune(history: &mut Vec<Entry>) -> bool {
+    let len = history.len();
+
+    if CONFIG.history.max_age_days != 0 {
+        let cutoff = now().saturating_sub(CONFIG.history.max_age_days * 24 * 60 * 60);
+        history.retain(|entry| entry.time >= cutoff);
+    }
+
+    if CONFIG.history.max_entries != 0 && history.len() > CONFIG.history.max_entries {
+        history.drain(..history.len() - CONFIG.history.max_entries);
+    }
+
+    history.len() != len
+}
+
+fn append(entry: &Entry) -> Result<(), String> {
+    let path = HISTORY_PATH.as_ref().ok_or("no data directory available")?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    line.push('\n');
+
+    open_private(path, OpenOptions::new().append(true))
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| e.to_string())
+}
+
+fn rewrite(history: &[Entry]) -> Result<(), String> {
+    let path = HISTORY_PATH.as_ref().ok_or("no data directory available")?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let mut text = String::new();
+    for entry in history {
+        text.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+        text.push('\n');
+    }
+
+    let tmp = path.with_extension("jsonl.tmp");
+    open_private(&tmp, OpenOptions::new().write(true).truncate(true))
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+/// Open `path`, creating it readable only by its owner on Unix since searches are private.
+fn open_private(path: &Path, options: &mut OpenOptions) -> io::Result<File> {
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(options, 0o600);
+
+    options.create(true).open(path)
+}
+
+/// Entries containing `search` in their query, shortcut or engine name, newest first.
+fn matching(search: &str) -> Vec<Entry> {
+    let search = search.to_lowercase();
+    let history = HISTORY.lock().unwrap_or_else(PoisonError::into_inner);
+
+    history
+        .iter()
+        .rev()
+        .filter(|entry| {
+            search.is_empty()
+                || entry.query.to_lowercase().contains(&search)
+                || entry.engine.to_lowercase().contains(&search)
+                || entry
+                    .shortcut
+                    .as_ref()
+                    .is_some_and(|s| s.to_lowercase().contains(&search))
+        })
+        .cloned()
+        .collect()
+}
+
+pub fn render_json() -> String {
+    serde_json::to_string(&matching("")).unwrap_or_default()
+}
+
+pub fn render_csv() -> String {
+    let mut output = String::from("time,shortcut,engine,query\n");
+
+    for entry in matching("") {
+        let _ = writeln!(
+            output,
+            "{},{},{},{}",
+            entry.time,
+            csv_field(entry.shortcut.as_deref().unwrap_or_default()),
+            csv_field(&entry.engine),
+            csv_field(&entry.query)
+        );
+    }
+
+    output
+}
+
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+pub fn render_html(search: &str) -> String {
+    let mut output = String::from(
+        r#"
+        <p><i><a href="/">Back to Main Page</a> - <a href="/history.json">Export as JSON</a> - <a href="/history.csv">Export as CSV</a></i></p>
+        <hr>
+        <h2>Search History</h2>
+    "#,
+    );
+
+    if !CONFIG.history.enabled {
+        output.push_str(
+            "<p>Search history is disabled. Set <code>enabled = true</code> in the <code>[history]</code> section of your configuration file to start recording.</p>",
+        );
+    }
+
+    if let Some(path) = HISTORY_PATH.as_ref() {
+        let _ = write!(output, "<p>Stored in <code>{path:?}</code></p>");
+    }
+
+    let _ = write!(
+        output,
+        r#"
+        <form action="/history" method="get">
+            <input type="search" name="q" value="{}" placeholder="Search history">
+            <button type="submit">Search</button>
+        </form>
+        <form action="/history/clear" method="post" onsubmit="return confirm('Clear all search history?');">
+            <button type="submit">Clear History</button>
+        </form>
+        "#,
+        escape_html(search)
+    );
+
+    let entries = matching(search);
+
+    if entries.is_empty() {
+        output.push_str("<p><i>No searches recorded.</i></p>");
+        return base_html(&output);
+    }
+
+    output.push_str(
+        r#"<table style="width: 100%; text-align: left;"><tr><th>Time</th><th>Shortcut</th><th>Engine</th><th>Query</th></tr>"#,
+    );
+
+    // search again wherever the service takes searches, which might not be `/?q=`
+    let search_path = CONFIG.search_paths.first().map_or("/", String::as_str);
+    let query_param = CONFIG.query_params.first().map_or("q", String::as_str);
+
+    for entry in entries.iter().take(MAX_DISPLAYED) {
+        let shortcut = entry
+            .shortcut
+            .as_ref()
+            .map(|s| format!("!{s}"))
+            .unwrap_or_default();
+        let search_again =
+            urlencoding::encode(format!("{shortcut} {}", entry.query).trim()).into_owned();
+
+        let _ = write!(
+            output,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td><a href=\"{}?{}={search_again}\">{}</a></td></tr>",
+            format_timestamp(entry.time),
+            escape_html(&shortcut),
+            escape_html(&entry.engine),
+            escape_html(search_path),
+            escape_html(&urlencoding::encode(query_param)),
+            escape_html(&entry.query)
+        );
+    }
+
+    output.push_str("</table>");
+
+    if entries.len() > MAX_DISPLAYED {
+        let _ = write!(
+            output,
+            "<p><i>Showing the {MAX_DISPLAYED} most recent of {} matching searches.</i></p>",
+            entries.len()
+        );
+    }
+
+    base_html(&output)
+}
@@ -0,0 +1,448 @@
+//! The `import` subcommand, turning browser keyword searches into `[engines]` entries.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+#[cfg(feature = "sqlite")]
+use rusqlite::Connection;
+
+use crate::CONFIG;
+
+/// A keyword search found in a browser profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyword {
+    pub keyword: String,
+    /// URL template with `{s}` in place of the search terms.
+    pub url: String,
+    pub title: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Import {
+    pub keywords: Vec<Keyword>,
+    /// Entries that couldn't be converted, and why.
+    pub skipped: Vec<String>,
+}
+
+/// Read keyword searches from a Firefox `places.sqlite`, a bookmarks HTML export, or a Chromium
+/// `Web Data` file, detected from its contents.
+pub fn read(path: &Path) -> Result<Import, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+
+    if bytes.starts_with(b"SQLite format 3\0") {
+        read_database(path)
+    } else {
+        let text = String::from_utf8_lossy(&bytes);
+
+        if text.contains("NETSCAPE-Bookmark-file") || text.contains("<DT><A") {
+            Ok(read_bookmarks_html(&text))
+        } else {
+            Err("not a places.sqlite, Web Data or bookmarks HTML file".into())
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn read_database(_path: &Path) -> Result<Import, String> {
+    Err("reading browser databases needs the `sqlite` feature, import a bookmarks HTML export instead".into())
+}
+
+#[cfg(feature = "sqlite")]
+fn read_database(path: &Path) -> Result<Import, String> {
+    // the browser keeps its database locked while running, so work on a copy, along with the
+    // write-ahead log holding changes that aren't in the database file yet
+    let dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let copy = dir.path().join("import.sqlite");
+    fs::copy(path, &copy).map_err(|e| e.to_string())?;
+
+    for suffix in ["-wal", "-shm"] {
+        let mut journal = path.as_os_str().to_owned();
+        journal.push(suffix);
+        let journal = PathBuf::from(journal);
+
+        if journal.exists() {
+            let mut journal_copy = copy.as_os_str().to_owned();
+            journal_copy.push(suffix);
+            fs::copy(&journal, journal_copy).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let db = Connection::open(&copy).map_err(|e| e.to_string())?;
+
+    if has_table(&db, "moz_keywords")? {
+        read_firefox(&db)
+    } else if has_table(&db, "keywords")? {
+        read_chromium(&db)
+    } else {
+        Err("database has no Firefox or Chromium keywords".into())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn has_table(db: &Connection, name: &str) -> Result<bool, String> {
+    db.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "sqlite")]
+fn read_firefox(db: &Connection) -> Result<Import, String> {
+    let mut statement = db
+        .prepare(
+            "SELECT k.keyword, p.url, k.post_data,
+                (SELECT b.title FROM moz_bookmarks b WHERE b.fk = p.id AND b.title IS NOT NULL LIMIT 1)
+            FROM moz_keywords k JOIN moz_places p ON p.id = k.place_id
+            ORDER BY k.id",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut import = Import::default();
+
+    for row in rows {
+        let (keyword, url, post_data, title) = row.map_err(|e| e.to_string())?;
+
+        if post_data.is_some_and(|data| !data.is_empty()) {
+            import.skipped.push(format!(
+                "{keyword}: searches with POST data aren't supported"
+            ));
+            continue;
+        }
+
+        import.push(keyword, firefox_template(&url), title.unwrap_or_default());
+    }
+
+    Ok(import)
+}
+
+#[cfg(feature = "sqlite")]
+fn read_chromium(db: &Connection) -> Result<Import, String> {
+    let mut statement = db
+        .prepare("SELECT keyword, url, short_name FROM keywords ORDER BY id")
+        .map_err(|e| e.to_string())?;
+
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut import = Import::default();
+
+    for row in rows {
+        let (keyword, url, title) = row.map_err(|e| e.to_string())?;
+        let url = url.replace("{searchTerms}", "{s}");
+
+        // placeholders only the browser can fill in, like `{google:baseURL}`
+        if url.replace("{s}", "").contains('{') {
+            import
+                .skipped
+                .push(format!("{keyword}: unsupported placeholder in {url}"));
+            continue;
+        }
+
+        import.push(keyword, url, title);
+    }
+
+    Ok(import)
+}
+
+static ANCHOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<A\s([^>]*)>(.*?)</A>").unwrap());
+
+static ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)([A-Z_]+)="([^"]*)""#).unwrap());
+
+fn read_bookmarks_html(html: &str) -> Import {
+    let mut import = Import::default();
+
+    for anchor in ANCHOR.captures_iter(html) {
+        let mut url = None;
+        let mut keyword = None;
+        let mut post_data = false;
+
+        for attribute in ATTRIBUTE.captures_iter(&anchor[1]) {
+            match attribute[1].to_ascii_uppercase().as_str() {
+                "HREF" => url = Some(unescape_html(&attribute[2])),
+                "SHORTCUTURL" => keyword = Some(unescape_html(&attribute[2])),
+                "POST_DATA" => post_data = !attribute[2].is_empty(),
+                _ => {}
+            }
+        }
+
+        let (Some(url), Some(keyword)) = (url, keyword) else {
+            continue;
+        };
+
+        if post_data {
+            import.skipped.push(format!(
+                "{keyword}: searches with POST data aren't supported"
+            ));
+            continue;
+        }
+
+        import.push(keyword, firefox_template(&url), unescape_html(&anchor[2]));
+    }
+
+    import
+}
+
+impl Import {
+    fn push(&mut self, keyword: String, url: String, title: String) {
+        let keyword = keyword.trim().trim_start_matches('!').to_lowercase();
+
+        if keyword.is_empty() || keyword.contains(char::is_whitespace) {
+            self.skipped
+                .push(format!("'{keyword}': not usable as a shortcut"));
+            return;
+        }
+
+        // bookmark files wrap long attributes, which browsers drop along with the indentation
+        let url: String = url.lines().map(str::trim).collect();
+        if url.is_empty() || url.contains(char::is_whitespace) {
+            self.skipped
+                .push(format!("{keyword}: '{url}' isn't a valid URL"));
+            return;
+        }
+
+        self.keywords.push(Keyword {
+            keyword,
+            url,
+            title: title.split_whitespace().collect::<Vec<_>>().join(" "),
+        });
+    }
+}
+
+/// Firefox marks the search terms with `%s`, or `%S` to insert them without encoding.
+fn firefox_template(url: &str) -> String {
+    url.replace("%s", "{s}").replace("%S", "{s}")
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Render keywords as an `[engines]` table, with notes about duplicates and shortcuts that
+/// built-in or already configured engines would take precedence over.
+pub fn render(keywords: &[Keyword]) -> (String, Vec<String>) {
+    let mut output = String::from("[engines]\n");
+    let mut notes = Vec::new();
+    let mut seen = HashSet::new();
+
+    for keyword in keywords {
+        if !seen.insert(&keyword.keyword) {
+            notes.push(format!(
+                "{}: duplicate, only the first one is kept",
+                keyword.keyword
+            ));
+            continue;
+        }
+
+        if let Some(builtin) = crate::ENGINES.get_engine(&keyword.keyword) {
+            notes.push(format!(
+                "{}: conflicts with the built-in !{} ({}), which takes precedence",
+                keyword.keyword, keyword.keyword, builtin.name
+            ));
+        } else if CONFIG.engines.get_engine(&keyword.keyword).is_some() {
+            notes.push(format!(
+                "{}: already in your configuration",
+                keyword.keyword
+            ));
+        }
+
+        if !keyword.url.contains("{s}") {
+            notes.push(format!(
+                "{}: has no search terms placeholder, it always opens the same page",
+                keyword.keyword
+            ));
+        }
+
+        let key = if keyword
+            .keyword
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        {
+            keyword.keyword.clone()
+        } else {
+            toml::Value::String(keyword.keyword.clone()).to_string()
+        };
+
+        output.push_str(&format!(
+            "{key} = {}",
+            toml::Value::String(keyword.url.clone())
+        ));
+        if !keyword.title.is_empty() {
+            output.push_str(&format!(" # {}", keyword.title.replace('\n', " ")));
+        }
+        output.push('\n');
+    }
+
+    (output, notes)
+}
+
+/// Run `import FILE...`, printing TOML to stdout and a report to stderr.
+pub fn run(paths: &[PathBuf]) -> Result<(), String> {
+    let mut keywords = Vec::new();
+    let mut failed = false;
+
+    for path in paths {
+        match read(path) {
+            Ok(import) => {
+                eprintln!(
+                    "{}: found {} keyword searches",
+                    path.display(),
+                    import.keywords.len()
+                );
+                for skipped in import.skipped {
+                    eprintln!("  skipped {skipped}");
+                }
+                keywords.extend(import.keywords);
+            }
+            Err(err) => {
+                eprintln!("{}: {err}", path.display());
+                failed = true;
+            }
+        }
+    }
+
+    let (toml, notes) = render(&keywords);
+
+    for note in notes {
+        eprintln!("  {note}");
+    }
+    print!("{toml}");
+
+    if failed {
+        Err("some files couldn't be imported".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn firefox_placeholders() {
+        let cases = [
+            ("https://example.org/?q=%s", "https://example.org/?q={s}"),
+            ("https://example.org/%S/", "https://example.org/{s}/"),
+            ("https://example.org/", "https://example.org/"),
+            (
+                "https://example.org/?q=%s&r=%s",
+                "https://example.org/?q={s}&r={s}",
+            ),
+            (
+                "https://example.org/?q=100%25",
+                "https://example.org/?q=100%25",
+            ),
+        ];
+
+        for (url, template) in cases {
+            assert_eq!(firefox_template(url), template);
+        }
+    }
+
+    #[test]
+    fn bookmarks_html() {
+        let import = read_bookmarks_html(
+            r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<DL><p>
+    <DT><A HREF="https://docs.rs/releases/search?query=%s" ADD_DATE="1" SHORTCUTURL="!DRS">docs.rs &amp; crates</A>
+    <DT><A HREF="https://example.org/">No keyword</A>
+    <DT><a href="https://example.org/post" shortcuturl="post" post_data="q=%s">Post</a>
+    <DT><A HREF="https://example.org/?a=1&amp;q=%s" SHORTCUTURL="two words">Spaced</A>
+    <DT><A HREF="https://example.org/
+        ?q=%S" SHORTCUTURL="ml">Multi
+        line</A>
+    <DT><A HREF="https://example.org/?q=%s and more" SHORTCUTURL="sp">Space</A>
+</DL><p>"#,
+        );
+
+        assert_eq!(
+            import.keywords,
+            [
+                Keyword {
+                    keyword: "drs".into(),
+                    url: "https://docs.rs/releases/search?query={s}".into(),
+                    title: "docs.rs & crates".into(),
+                },
+                Keyword {
+                    keyword: "ml".into(),
+                    url: "https://example.org/?q={s}".into(),
+                    title: "Multi line".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            import.skipped,
+            [
+                "post: searches with POST data aren't supported",
+                "'two words': not usable as a shortcut",
+                "sp: 'https://example.org/?q={s} and more' isn't a valid URL",
+            ]
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn firefox_write_ahead_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("places.sqlite");
+
+        // kept open so the last insert stays in `places.sqlite-wal`
+        let db = Connection::open(&path).unwrap();
+        db.execute_batch(
+            "PRAGMA journal_mode = WAL;
+            CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT);
+            CREATE TABLE moz_keywords (id INTEGER PRIMARY KEY, keyword TEXT, place_id INTEGER, post_data TEXT);
+            CREATE TABLE moz_bookmarks (id INTEGER PRIMARY KEY, fk INTEGER, title TEXT);
+            PRAGMA wal_checkpoint;
+            INSERT INTO moz_places VALUES (1, 'https://example.org/?q=%s');
+            INSERT INTO moz_keywords VALUES (1, 'ex', 1, NULL);
+            INSERT INTO moz_bookmarks VALUES (1, 1, 'Example');",
+        )
+        .unwrap();
+        assert!(dir.path().join("places.sqlite-wal").exists());
+
+        let import = read(&path).unwrap();
+        assert_eq!(
+            import.keywords,
+            [Keyword {
+                keyword: "ex".into(),
+                url: "https://example.org/?q={s}".into(),
+                title: "Example".into(),
+            }]
+        );
+
+        drop(db);
+    }
+}
@@ -1,78 +1,313 @@
-use std::{cmp::Ordering, collections::HashMap, fmt::Write, sync::LazyLock};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    sync::LazyLock,
+};
 
 use compact_str::{CompactString, ToCompactString};
+use time::UtcDateTime;
 
-use crate::{config::CONFIG_CHECKS, engines::SearchEngineRef, CONFIG};
+use crate::{
+    config::CONFIG_CHECKS,
+    engines::{SearchEngineRef, SearchGroupRef},
+    frecency, CONFIG,
+};
 
 const EXAMPLE_CONFIG: &str = include_str!("../local-search-shortcuts.toml");
 
-pub static INDEX: LazyLock<String> = LazyLock::new(|| {
+pub fn index() -> String {
     let check_paths = CONFIG_CHECKS
         .iter()
-        .map(|path| path.canonicalize().unwrap_or(path.clone()))
-        .fold(String::new(), |mut output, path| {
-            let _ = write!(output, "<li><code>{path:?}</code></li>");
+        .map(|(layer, path)| (layer, path.canonicalize().unwrap_or(path.clone())))
+        .fold(String::new(), |mut output, (layer, path)| {
+            let _ = write!(output, "<li>{layer}: <code>{path:?}</code></li>");
             output
         });
 
-    let active_config = CONFIG
-        .path
-        .as_ref()
-        .map(|path| {
-            path.canonicalize()
-                .unwrap_or(path.clone())
-                .to_string_lossy()
-                .to_string()
-        })
-        .map(|path| format!("<code>{path}</code>"))
-        .unwrap_or("<b>None detected, using defaults</b>".into());
-
-    let port = CONFIG.port;
+    let active_config = if CONFIG.layers.is_empty() {
+        "<b>None detected, using defaults</b>".to_string()
+    } else {
+        CONFIG
+            .layers
+            .iter()
+            .fold(String::from("<ul>"), |mut output, (layer, path)| {
+                let _ = write!(
+                    output,
+                    "<li>{layer}: <code>{}</code></li>",
+                    escape_html(&path.to_string_lossy())
+                );
+                output
+            })
+            + "</ul>"
+    };
+
+    let included = CONFIG
+        .files
+        .iter()
+        .filter(|file| !CONFIG.layers.iter().any(|(_, path)| path == *file))
+        .fold(String::new(), |mut output, path| {
+            let _ = write!(
+                output,
+                "<li><code>{}</code></li>",
+                escape_html(&path.to_string_lossy())
+            );
+            output
+        });
+    let included = if included.is_empty() {
+        "None".to_string()
+    } else {
+        format!("<ul>{included}</ul>")
+    };
+
+    // values aren't shown, they may be secrets like `LSS_ACCESS_TOKEN`
+    let variables = if CONFIG.variables.is_empty() {
+        "None".to_string()
+    } else {
+        CONFIG
+            .variables
+            .iter()
+            .fold(String::from("<ul>"), |mut output, (name, setting)| {
+                let _ = write!(
+                    output,
+                    "<li><code>{}</code> &rarr; <code>{}</code></li>",
+                    escape_html(name),
+                    escape_html(setting)
+                );
+                output
+            })
+            + "</ul>"
+    };
+
+    let origins = CONFIG
+        .origins
+        .iter()
+        .fold(String::new(), |mut output, (setting, layers)| {
+            let layers = layers
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = write!(
+                output,
+                "<li><code>{}</code>: {layers}</li>",
+                escape_html(setting)
+            );
+            output
+        });
+    let origins = if origins.is_empty() {
+        "All defaults".to_string()
+    } else {
+        format!("<ul>{origins}</ul>")
+    };
+
+    let errors = if CONFIG.errors.is_empty() {
+        String::new()
+    } else {
+        CONFIG.errors.iter().fold(
+            String::from("<p><b>Problems:</b></p><ul>"),
+            |mut output, error| {
+                let _ = write!(output, "<li>{}</li>", escape_html(error));
+                output
+            },
+        ) + "</ul>"
+    };
+
+    let base_url = CONFIG.base_url();
+    let most_used = most_used();
 
     let default_name = &CONFIG.default_engine.name;
     let default_url = &CONFIG.default_engine.url.replace("{s}", "");
 
+    let rules = if CONFIG.rules.is_empty() {
+        "None".to_string()
+    } else {
+        CONFIG
+            .rules
+            .iter()
+            .fold(String::from("<ol>"), |mut output, rule| {
+                let _ = write!(
+                    output,
+                    "<li><code>{}</code> &rarr; {}</li>",
+                    escape_html(rule.pattern.as_str()),
+                    escape_html(rule.target_name())
+                );
+                output
+            })
+            + "</ol>"
+    };
+
+    let navigation = if CONFIG.navigation.enabled {
+        format!(
+            "Enabled, defaulting to <code>{}://</code> (start a query with <code>?</code> to search instead)",
+            escape_html(&CONFIG.navigation.scheme)
+        )
+    } else {
+        "Disabled".to_string()
+    };
+
+    let allowed_clients = if !CONFIG.broadcast {
+        "Local only".to_string()
+    } else if CONFIG.access.allow.is_empty() {
+        "<b>Anyone on the network</b>".to_string()
+    } else {
+        CONFIG
+            .access
+            .allow
+            .iter()
+            .map(|cidr| format!("<code>{cidr}</code>"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
     base_html(&format!(
         r#"
         <p>
             <a href="/info">List of Available Shortcuts</a>
             -
+            <a href="/stats">Usage Statistics</a>
+            -
+            <a href="/history">Search History</a>
+            -
             <i>Search <code>!info</code> to view this page at any time.</i>
         </p>
         <hr>
+        {most_used}
         <h2>Instructions:</h2>
         <p>Just set this as the search engine in your browser:</p>
-        <pre>http://localhost:{port}/?q=[TERMS]</pre>
+        <pre>{base_url}/?q=[TERMS]</pre>
         <p>Then use the many search engine shortcuts like so:</p>
         <pre>!w Hello World</pre>
         <p>This redirects to the relevant Wikipedia page or search results.</p>
         <p><i>(the placement of the shortcut is not important, and the first one found is always used)</i></p>
+        <p>Shortcut completions, ranked by how often and how recently you use them, are available as search suggestions at:</p>
+        <pre>{base_url}/suggest?q=[TERMS]</pre>
         <hr>
         <h2>Configuration</h2>
         <h3>Current Configuration</h3>
-        <p>Configuration File: {active_config}</p>
+        {errors}
+        <p>Configuration Layers: {active_config}</p>
+        <p>Included Files: {included}</p>
+        <p>Environment Overrides: {variables}</p>
         <p>Default Search Engine: <a href={default_url}>{default_name}</a></p>
+        <p>Direct Navigation: {navigation}</p>
+        <p>Fallback Rules: {rules}</p>
+        <p>Allowed Clients: {allowed_clients}</p>
+        <p>Settings by Layer: {origins}</p>
         <h3>Configuration Options</h3>
         <p>Here's an example configuration file:</p>
         <pre>{EXAMPLE_CONFIG}</pre>
-        <p>Configuration files are read in this order:</p>
+        <p>Configuration files are read in this order, later ones overriding earlier ones:</p>
         <ul>{check_paths}</ul>
         <hr>
         <p><i>Default engines retrieved from DuckDuckGo on {}</i></p>
     "#,
         include_str!(env!("LSS_LAST_UPDATED"))
     ))
-});
+}
+
+/// The "Your most used shortcuts" section, empty when nothing has been recorded.
+fn most_used() -> String {
+    const SHOWN: usize = 10;
+
+    let shortcuts = frecency::ranked()
+        .into_iter()
+        .filter_map(|(shortcut, count)| {
+            crate::ENGINES
+                .get_engine(&shortcut)
+                .or(CONFIG.engines.get_engine(&shortcut))
+                .map(|engine| (shortcut, count, engine))
+        })
+        .take(SHOWN)
+        .fold(String::new(), |mut output, (shortcut, count, engine)| {
+            let uses = if count == 1 { "use" } else { "uses" };
+            let _ = write!(
+                output,
+                "<li><a href=\"{}\">{}</a>: !{} <i>({count} {uses})</i></li>",
+                engine.url.replace("{s}", ""),
+                escape_html(engine.name),
+                escape_html(&shortcut)
+            );
+            output
+        });
+
+    if shortcuts.is_empty() {
+        return String::new();
+    }
+
+    format!("<h2>Your Most Used Shortcuts</h2><ul>{shortcuts}</ul><hr>")
+}
+
+/// Opens every engine of a shortcut group, the first one in the current tab.
+/// A page opening every engine in `launch`, which mustn't be empty, for the group `name`.
+pub fn launcher(name: &str, launch: &[(String, String)]) -> String {
+    let links = launch
+        .iter()
+        .fold(String::new(), |mut output, (name, url)| {
+            let _ = write!(
+                output,
+                "<li><a href=\"{}\" target=\"_blank\" rel=\"noopener\">{}</a></li>",
+                escape_html(url),
+                escape_html(name)
+            );
+            output
+        });
+
+    let urls: Vec<&str> = launch.iter().map(|(_, url)| url.as_str()).collect();
+    // unwrap: a list of strings always serializes
+    let urls = serde_json::to_string(&urls).unwrap().replace("</", "<\\/");
+
+    base_html(&format!(
+        r#"
+        <h2>Searching {} engines with {}</h2>
+        <ul>{links}</ul>
+        <p><button onclick="openAll()">Open All</button></p>
+        <p><i>If only one opened, allow pop-ups for this page.</i></p>
+        <script>
+            function openAll() {{
+                const urls = {urls};
+                urls.slice(1).forEach((url) => window.open(url, "_blank", "noopener"));
+                window.location.replace(urls[0]);
+            }}
+            openAll();
+        </script>
+    "#,
+        launch.len(),
+        escape_html(name)
+    ))
+}
 
 pub static NOT_FOUND: LazyLock<String> =
     LazyLock::new(|| base_html("<h2>Error 404: Page Doesn't Exist</h2>"));
 
+pub static EMPTY_GROUP: LazyLock<String> =
+    LazyLock::new(|| base_html("<h2>Error 404: None of This Group's Engines Exist</h2>"));
+
+pub static BAD_REQUEST: LazyLock<String> =
+    LazyLock::new(|| base_html("<h2>Error 400: Bad Request</h2>"));
+
+pub static PAYLOAD_TOO_LARGE: LazyLock<String> =
+    LazyLock::new(|| base_html("<h2>Error 413: Search Too Long</h2>"));
+
+pub static UNSUPPORTED_MEDIA_TYPE: LazyLock<String> =
+    LazyLock::new(|| base_html("<h2>Error 415: Searches Must Be Submitted As Form Data</h2>"));
+
+pub static FORBIDDEN: LazyLock<String> =
+    LazyLock::new(|| base_html("<h2>Error 403: Access Denied</h2>"));
+
+pub static UNAUTHORIZED: LazyLock<String> =
+    LazyLock::new(|| base_html("<h2>Error 401: Sign In Required</h2>"));
+
 pub static INFO: LazyLock<String> =
     LazyLock::new(|| base_html(&render_categories(generate_categories())));
 
 struct EngineDescription {
     name: CompactString,
     shortcuts: String,
+    /// Member shortcuts, for groups.
+    members: Option<String>,
+    /// A built-in engine sent to HTTPS instead of its plain HTTP URL.
+    upgraded: bool,
 }
 
 type Subcategory = HashMap<String, EngineDescription>;
@@ -80,11 +315,27 @@ type Category = HashMap<String, Subcategory>;
 
 const UNCATEGORIZED: &str = "Uncategorized";
 const CUSTOM: &str = "Custom";
+const GROUPS: &str = "Groups";
 
 fn generate_categories() -> Vec<(String, Category)> {
     let mut categories: HashMap<String, Category> = HashMap::new();
 
+    let upgraded_at_build: HashSet<String> =
+        crate::dump::upgraded().map(str::to_lowercase).collect();
+
     for (shortcuts, engine) in crate::ENGINES.engines() {
+        // overridden by a later bang source
+        if shortcuts.is_empty() {
+            continue;
+        }
+
+        let https_url = shortcuts
+            .iter()
+            .map(|s| crate::CONFIG.https.url(s, engine.url))
+            .find(|url| url != engine.url);
+        let upgraded =
+            https_url.is_some() || shortcuts.iter().any(|s| upgraded_at_build.contains(*s));
+
         let category_name = engine
             .category
             .map(|s| s.to_string())
@@ -95,7 +346,9 @@ fn generate_categories() -> Vec<(String, Category)> {
             .map(|s| s.to_string())
             .unwrap_or_default();
 
-        let (url, description) = map_engine((shortcuts, engine));
+        let (url, mut description) = map_engine((shortcuts, engine));
+        let url = https_url.map_or(url, |url| url.replace("{s}", ""));
+        description.upgraded = upgraded;
 
         categories
             .entry(category_name)
@@ -105,12 +358,22 @@ fn generate_categories() -> Vec<(String, Category)> {
             .insert(url, description);
     }
 
+    let builtin_groups: Subcategory = crate::ENGINES.groups().map(map_group).collect();
+    if !builtin_groups.is_empty() {
+        categories
+            .entry(GROUPS.to_string())
+            .or_default()
+            .insert(String::new(), builtin_groups);
+    }
+
     let custom = crate::CONFIG.engines.engines().map(map_engine).collect();
+    let custom_groups: Subcategory = crate::CONFIG.engines.groups().map(map_group).collect();
 
-    categories
-        .entry(CUSTOM.to_string())
-        .or_default()
-        .insert(String::new(), custom);
+    let custom_category = categories.entry(CUSTOM.to_string()).or_default();
+    custom_category.insert(String::new(), custom);
+    if !custom_groups.is_empty() {
+        custom_category.insert(GROUPS.to_string(), custom_groups);
+    }
 
     let mut categories: Vec<(String, Category)> = categories.into_iter().collect();
 
@@ -142,6 +405,28 @@ fn map_engine((shortcuts, engine): (Vec<&str>, SearchEngineRef)) -> (String, Eng
         EngineDescription {
             name: engine.name.to_compact_string(),
             shortcuts,
+            members: None,
+            upgraded: false,
+        },
+    )
+}
+
+/// Groups have no URL of their own, so they're keyed by shortcut instead.
+fn map_group((shortcut, group): (&str, SearchGroupRef)) -> (String, EngineDescription) {
+    let members = group
+        .members
+        .iter()
+        .map(|m| format!("!{m}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    (
+        format!("!{shortcut}"),
+        EngineDescription {
+            name: group.name.to_compact_string(),
+            shortcuts: format!("!{shortcut}"),
+            members: Some(members),
+            upgraded: false,
         },
     )
 }
@@ -154,20 +439,37 @@ fn render_categories(categories: Vec<(String, Category)>) -> String {
         <p><i><a href="/">Back to Main Page</a></i></p>
         <hr>
         <div style="text-align: left;">
-        <h3>Categories</h3>
-        <ol>
     "#,
     );
 
+    let upgraded = categories
+        .iter()
+        .flat_map(|(_, subcategories)| subcategories.values())
+        .flat_map(HashMap::values)
+        .filter(|engine| engine.upgraded)
+        .count();
+    if upgraded > 0 {
+        write!(
+            output,
+            "<p>{upgraded} built-in engines are upgraded to HTTPS, marked below.</p>"
+        )
+        .unwrap();
+    }
+
+    output.push_str("<h3>Categories</h3><ol>");
+
+    // names come from config files and bang sources, so they're escaped like any other input
     for (category, subcategories) in &categories {
-        let category_id = category.replace(' ', "_");
+        let category_id = escape_html(&category.replace(' ', "_"));
+        let category = escape_html(category);
         write!(output, "<li><a href=\"#{category_id}\">{category}</a><ul>").unwrap();
 
         for (subcategory, _) in subcategories.iter().filter(|(s, _)| !s.is_empty()) {
             write!(
                 output,
-                "<li><a href=\"#{category_id}_{}\">{subcategory}</a></li>",
-                subcategory.replace(' ', "_")
+                "<li><a href=\"#{category_id}_{}\">{}</a></li>",
+                escape_html(&subcategory.replace(' ', "_")),
+                escape_html(subcategory)
             )
             .unwrap();
         }
@@ -178,20 +480,44 @@ fn render_categories(categories: Vec<(String, Category)>) -> String {
     output.push_str(r#"</ol>"#);
 
     for (category, subcategories) in categories {
-        let category_id = category.replace(' ', "_");
+        let category_id = escape_html(&category.replace(' ', "_"));
+        let category = escape_html(&category);
         write!(output, "<hr><h2 id=\"{category_id}\">{category}</h3>").unwrap();
 
         for (subcategory, engines) in subcategories {
-            let subcategory_id = format!("{category_id}_{}", subcategory.replace(' ', "_"));
+            let subcategory_id = format!(
+                "{category_id}_{}",
+                escape_html(&subcategory.replace(' ', "_"))
+            );
+            let subcategory = escape_html(&subcategory);
             write!(output, "<h3 id=\"{subcategory_id}\">{subcategory}</h4>").unwrap();
 
             output.push_str("<ul>");
 
             for (url, engine) in engines {
+                if let Some(members) = engine.members {
+                    write!(
+                        output,
+                        "<li>{}: {} &rarr; {}</li>",
+                        escape_html(&engine.name),
+                        escape_html(&engine.shortcuts),
+                        escape_html(&members)
+                    )
+                    .unwrap();
+                    continue;
+                }
+
                 write!(
                     output,
-                    "<li><a href=\"{url}\">{}</a>: {}</li>",
-                    engine.name, engine.shortcuts
+                    "<li><a href=\"{}\">{}</a>: {}{}</li>",
+                    escape_html(&url),
+                    escape_html(&engine.name),
+                    escape_html(&engine.shortcuts),
+                    if engine.upgraded {
+                        " <i>(upgraded to HTTPS)</i>"
+                    } else {
+                        ""
+                    }
                 )
                 .unwrap();
             }
@@ -266,3 +592,26 @@ pub(crate) fn base_html(content: &str) -> String {
         env!("CARGO_PKG_VERSION")
     )
 }
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Format seconds since the unix epoch like `2025-04-01 13:05 UTC`.
+pub(crate) fn format_timestamp(secs: u64) -> String {
+    let Ok(t) = UtcDateTime::from_unix_timestamp(secs as i64) else {
+        return "Unknown".into();
+    };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        t.year(),
+        t.month() as u8,
+        t.day(),
+        t.hour(),
+        t.minute()
+    )
+}
@@ -0,0 +1,382 @@
+//! Checks `build.rs` runs over every bang it reads, shared with `dump` which shows the findings.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::{bangs::Bang, engines::Segment};
+
+/// Fails the build when a finding is at least this severe, or never if set to `never`.
+#[allow(dead_code)]
+pub const FAIL_ON_VAR: &str = "LSS_LINT_FAIL_ON";
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(Self::Info),
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "unknown severity '{s}', expected info, warning, error or never"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// A shortcut that was already taken, the later bang replaces the earlier one.
+    Duplicate,
+    /// No `{s}`, so the search terms are dropped.
+    MissingPlaceholder,
+    /// A plain `http://` URL.
+    Insecure,
+    /// Not an absolute HTTP(S) URL.
+    InvalidUrl,
+    /// Spaces, quotes or backslashes, which browsers escape or mangle.
+    Unescaped,
+    /// Literal text that looks like a placeholder, like a stray `{s}` or doubled braces.
+    AmbiguousPlaceholder,
+    /// A placeholder that isn't supported, left in redirects as is.
+    UnsupportedPlaceholder,
+}
+
+impl Kind {
+    #[allow(dead_code)]
+    pub fn severity(self) -> Severity {
+        match self {
+            Self::MissingPlaceholder | Self::Insecure => Severity::Info,
+            Self::Duplicate
+            | Self::Unescaped
+            | Self::AmbiguousPlaceholder
+            | Self::UnsupportedPlaceholder => Severity::Warning,
+            Self::InvalidUrl => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Duplicate => "duplicate",
+            Self::MissingPlaceholder => "missing_placeholder",
+            Self::Insecure => "insecure",
+            Self::InvalidUrl => "invalid_url",
+            Self::Unescaped => "unescaped",
+            Self::AmbiguousPlaceholder => "ambiguous_placeholder",
+            Self::UnsupportedPlaceholder => "unsupported_placeholder",
+        })
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Lint {
+    pub kind: Kind,
+    pub severity: Severity,
+    pub shortcut: String,
+    pub source: String,
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} !{} ({}): {}",
+            self.severity, self.kind, self.shortcut, self.source, self.message
+        )
+    }
+}
+
+/// Collects findings for bangs in the order they're inserted into the database.
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+pub struct Linter {
+    /// Lowercased shortcut to the source and name of the bang holding it.
+    seen: HashMap<String, (String, String)>,
+    pub lints: Vec<Lint>,
+}
+
+#[allow(dead_code)]
+impl Linter {
+    pub fn check(&mut self, source: &str, bang: &Bang) {
+        let mut push = |kind: Kind, message: String| {
+            self.lints.push(Lint {
+                kind,
+                severity: kind.severity(),
+                shortcut: bang.shortcut.clone(),
+                source: source.to_string(),
+                message,
+            })
+        };
+
+        let url = &bang.url;
+
+        if !url.contains("{s}") {
+            push(
+                Kind::MissingPlaceholder,
+                format!("{url} has no {{s}}, the search terms are dropped"),
+            );
+        }
+
+        if url.starts_with("http://") {
+            push(Kind::Insecure, format!("{url} isn't HTTPS"));
+        }
+
+        if let Err(reason) = check_url(url) {
+            push(Kind::InvalidUrl, format!("{url} {reason}"));
+        }
+
+        if url.contains(|c: char| c.is_whitespace() || c == '\\' || c == '"') {
+            push(
+                Kind::Unescaped,
+                format!("'{url}' has spaces, quotes or backslashes"),
+            );
+        }
+
+        if is_ambiguous(&bang.template) {
+            push(
+                Kind::AmbiguousPlaceholder,
+                format!("{url} has literal text that looks like a placeholder"),
+            );
+        }
+
+        for placeholder in &bang.unsupported {
+            push(
+                Kind::UnsupportedPlaceholder,
+                format!("{url} uses {placeholder}, which is left as is"),
+            );
+        }
+
+        let previous = self.seen.insert(
+            bang.shortcut.to_lowercase(),
+            (source.to_string(), bang.name.clone()),
+        );
+        if let Some((previous_source, previous_name)) = previous {
+            push(
+                Kind::Duplicate,
+                format!(
+                    "replaces {previous_name} from {previous_source} with {}",
+                    bang.name
+                ),
+            );
+        }
+    }
+
+    /// Findings at least as severe as `severity`.
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &Lint> {
+        self.lints.iter().filter(move |l| l.severity >= severity)
+    }
+}
+
+/// Literal text with `{s}` or doubled braces, or braces right next to the search terms, which
+/// usually means the source's template syntax was mistyped.
+#[allow(dead_code)]
+fn is_ambiguous(template: &[Segment]) -> bool {
+    template.iter().enumerate().any(|(idx, segment)| {
+        let Segment::Literal(literal) = segment else {
+            return false;
+        };
+
+        let after_terms = idx > 0 && template[idx - 1] == Segment::Terms;
+        let before_terms = template.get(idx + 1) == Some(&Segment::Terms);
+
+        literal.contains("{s}")
+            || literal.contains("{{")
+            || literal.contains("}}")
+            || (after_terms && literal.starts_with('}'))
+            || (before_terms && literal.ends_with('{'))
+    })
+}
+
+#[allow(dead_code)]
+fn check_url(url: &str) -> Result<(), &'static str> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or("isn't an absolute HTTP(S) URL")?;
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+
+    // `{s}` may stand in for a subdomain, like `{s}.wikipedia.org`, and internationalized
+    // domain names are fine unencoded
+    let host = host.replace("{s}", "x");
+    if host.is_empty()
+        || !host
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-._:[]".contains(c))
+    {
+        return Err("has an invalid host");
+    }
+
+    if url.contains(|c: char| c.is_control()) {
+        return Err("has control characters");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bang(shortcut: &str, url: &str) -> Bang {
+        let (template, unsupported) = crate::bangs::parse_duckduckgo_template(url);
+
+        Bang {
+            shortcut: shortcut.to_string(),
+            name: shortcut.to_string(),
+            url: Segment::to_url(&template),
+            template,
+            unsupported,
+            category: None,
+            subcategory: None,
+        }
+    }
+
+    fn kinds(url: &str) -> Vec<Kind> {
+        let mut linter = Linter::default();
+        linter.check("test", &bang("t", url));
+        linter.lints.iter().map(|lint| lint.kind).collect()
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(kinds("https://example.org/search?q={{{s}}}"), []);
+        assert_eq!(kinds("https://{{{s}}}.wikipedia.org/"), []);
+        assert_eq!(kinds("https://例え.jp/検索?q={{{s}}}"), []);
+        assert_eq!(kinds("https://user@[::1]:8080/?q={{{s}}}"), []);
+    }
+
+    #[test]
+    fn duplicate() {
+        let mut linter = Linter::default();
+        linter.check("a", &bang("g", "https://example.org/?q={{{s}}}"));
+        linter.check("b", &bang("G", "https://example.com/?q={{{s}}}"));
+
+        assert_eq!(linter.lints.len(), 1);
+        assert_eq!(linter.lints[0].kind, Kind::Duplicate);
+        assert_eq!(linter.lints[0].source, "b");
+    }
+
+    #[test]
+    fn missing_placeholder() {
+        assert_eq!(kinds("https://example.org/"), [Kind::MissingPlaceholder]);
+    }
+
+    #[test]
+    fn insecure() {
+        assert_eq!(kinds("http://example.org/?q={{{s}}}"), [Kind::Insecure]);
+    }
+
+    #[test]
+    fn invalid_url() {
+        assert_eq!(kinds("example.org/?q={{{s}}}"), [Kind::InvalidUrl]);
+        assert_eq!(kinds("ftp://example.org/?q={{{s}}}"), [Kind::InvalidUrl]);
+        assert_eq!(kinds("https:///?q={{{s}}}"), [Kind::InvalidUrl]);
+        assert_eq!(kinds("https://exa!mple.org/?q={{{s}}}"), [Kind::InvalidUrl]);
+        assert_eq!(
+            kinds("https://example.org/\u{7}?q={{{s}}}"),
+            [Kind::InvalidUrl]
+        );
+    }
+
+    #[test]
+    fn unescaped() {
+        assert_eq!(
+            kinds("https://example.org/?q={{{s}}}&x=a b"),
+            [Kind::Unescaped]
+        );
+        assert_eq!(
+            kinds("https://example.org/?q=\"{{{s}}}\""),
+            [Kind::Unescaped]
+        );
+    }
+
+    #[test]
+    fn ambiguous_placeholder() {
+        assert_eq!(
+            kinds("https://example.org/?q={{{{{s}}}}}"),
+            [Kind::AmbiguousPlaceholder]
+        );
+        // a stray `{s}` is also an unsupported single braced name
+        assert_eq!(
+            kinds("https://example.org/?q={{{s}}}&r={s}"),
+            [Kind::AmbiguousPlaceholder, Kind::UnsupportedPlaceholder]
+        );
+        // braces in JSON aren't next to the search terms
+        assert_eq!(
+            kinds("https://example.org/?q={\"a\":\"{{{s}}}\"}"),
+            [Kind::Unescaped]
+        );
+    }
+
+    #[test]
+    fn unsupported_placeholder() {
+        assert_eq!(
+            kinds("https://example.org/?q={{{s}}}&hl={lang}"),
+            [Kind::UnsupportedPlaceholder]
+        );
+        assert_eq!(
+            kinds("https://example.org/?q={{{s}}}&ie={inputEncoding}"),
+            []
+        );
+    }
+
+    #[test]
+    fn ambiguous() {
+        let cases = [
+            ("https://www.google.com/search?q={{{s}}}", false),
+            (
+                "http://www.edbpriser.dk/Search/General.aspx?q={{{s}}}}",
+                true,
+            ),
+            (
+                "https://www.cdw.ca/shop/search/result.aspx?key={{{s}}}}&wclsscat=",
+                true,
+            ),
+            (
+                "https://www.congress.gov/search?q={%22search%22%3A%22{{{s}}}%22}",
+                false,
+            ),
+            ("https://example.org/?q={{{{s}}}", true),
+            ("https://example.org/?q={{{s}}}&r={s}", true),
+            ("https://example.org/?json={{\"q\":\"{{{s}}}\"}}", true),
+        ];
+
+        for (url, ambiguous) in cases {
+            let (template, _) = crate::bangs::parse_duckduckgo_template(url);
+            assert_eq!(is_ambiguous(&template), ambiguous, "{url}");
+        }
+    }
+
+    #[test]
+    fn severities() {
+        assert!(Kind::InvalidUrl.severity() > Kind::Unescaped.severity());
+        assert!(Kind::Duplicate.severity() > Kind::Insecure.severity());
+        assert_eq!("warning".parse(), Ok(Severity::Warning));
+        assert!("never".parse::<Severity>().is_err());
+    }
+}
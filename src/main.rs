@@ -1,19 +1,132 @@
-use std::{io::Cursor, sync::LazyLock, thread};
+use std::{
+    env, fs,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+    process,
+    sync::LazyLock,
+    thread,
+    time::Instant,
+};
 
-use tiny_http::{Header, Request, Response, Server, StatusCode};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
 
+mod access;
+mod bangs;
 mod config;
+mod dump;
 mod engines;
+mod export;
+mod frecency;
+mod history;
+mod import;
 mod info;
+mod lint;
+mod metrics;
+mod navigate;
+mod rules;
+mod stats;
+mod suggest;
+mod terms;
+mod tls;
 
 use config::CONFIG;
 
-static ENGINES: LazyLock<&engines::ArchivedSearchEngineDatabase> =
-    LazyLock::new(|| unsafe { rkyv::access_unchecked(&include_bytes!(env!("LSS_DATABASE"))[..]) });
+#[repr(C, align(16))]
+struct Aligned<T: ?Sized>(T);
+
+static EMBEDDED_DATABASE: &Aligned<[u8]> = &Aligned(*include_bytes!(env!("LSS_DATABASE")));
+
+static ENGINES: LazyLock<&engines::ArchivedSearchEngineDatabase> = LazyLock::new(|| {
+    let base = env::var_os("LSS_DATABASE_PATH")
+        .and_then(|path| match load_database(Path::new(&path)) {
+            Ok(db) => {
+                tracing::info!("loaded search engine database {path:?}");
+                Some(db)
+            }
+            Err(err) => {
+                tracing::error!(
+                    "failed to load search engine database {path:?}: {err}, using built-in engines"
+                );
+                None
+            }
+        })
+        .unwrap_or_else(|| {
+            engines::ArchivedSearchEngineDatabase::from_bytes(&EMBEDDED_DATABASE.0)
+                .expect("built-in search engine database is invalid")
+        });
+
+    let Ok(sources) = env::var(bangs::SOURCES_VAR) else {
+        return base;
+    };
+
+    match with_sources(base, &sources) {
+        Ok(db) => db,
+        Err(err) => {
+            tracing::error!("failed to load bang sources: {err}, using built-in engines");
+            base
+        }
+    }
+});
+
+const USAGE: &str = "\
+Usage: local-search-shortcuts [--config FILE] [COMMAND]
+
+Options:
+  --config FILE               Layer FILE over the other config files, for any command
+
+Commands:
+  (none)                      Run the search shortcut service
+  generate-certificate [DIR]  Write a self-signed localhost certificate and key to DIR
+      --force                 Replace an existing certificate and key
+  dump FORMAT [OPTIONS]       Write the search engine database as json or toml, with what the
+                              build skipped or rewrote
+      --custom                Include custom engines from the config files
+      --output PATH           Write to PATH instead of stdout
+  export FORMAT [OPTIONS]     Write engines as bookmarks (HTML with keywords), chromium (site
+                              search JSON) or opensearch (one XML file per shortcut)
+      --builtin | --all       Export built-in engines, or both, instead of custom ones
+      --filter REGEX          Only export shortcuts or names matching REGEX
+      --output PATH           Write to PATH instead of stdout, required for opensearch
+  import FILE...              Print [engines] entries for the keyword searches in Firefox
+                              places.sqlite or bookmarks HTML, or Chromium Web Data files
+  help                        Show this message";
 
 fn main() {
-    tracing_subscriber::fmt::init();
+    let mut args: Vec<String> = env::args().skip(1).collect();
 
+    // `--config` can go anywhere, so it also applies to `dump` and `export`
+    if let Some(idx) = args.iter().position(|arg| arg == "--config") {
+        if idx + 1 >= args.len() {
+            eprintln!("--config needs a file\n\n{USAGE}");
+            process::exit(2);
+        }
+        let path = args.remove(idx + 1);
+        args.remove(idx);
+        let _ = config::CLI_CONFIG.set(PathBuf::from(path));
+    }
+
+    // other subcommands print their results, keep logs out of them
+    if args.is_empty() {
+        tracing_subscriber::fmt::init();
+    } else {
+        tracing_subscriber::fmt().with_writer(io::stderr).init();
+    }
+
+    match args.first().map(String::as_str) {
+        None => serve(),
+        Some("generate-certificate") => generate_certificate(&args[1..]),
+        Some("dump") => dump(&args[1..]),
+        Some("export") => export(&args[1..]),
+        Some("import") => import(&args[1..]),
+        Some("help" | "-h" | "--help") => println!("{USAGE}"),
+        Some(command) => {
+            eprintln!("unknown command '{command}'\n\n{USAGE}");
+            process::exit(2);
+        }
+    }
+}
+
+fn serve() {
     // just for a fast first search, isn't necessary
     let _ = LazyLock::force(&ENGINES);
     let _ = LazyLock::force(&CONFIG);
@@ -22,19 +135,65 @@ fn main() {
         "loaded {} search engines",
         ENGINES.engine_count() + CONFIG.engines.engine_count()
     );
-    tracing::info!("launching service at http://{}/", CONFIG.addr());
 
-    let server = match Server::http(CONFIG.addr()) {
-        Ok(server) => server,
-        Err(e) => {
-            tracing::error!("failed to start service: {e}");
-            return;
+    if CONFIG.broadcast
+        && CONFIG.access.allow.is_empty()
+        && CONFIG.access.token.is_none()
+        && CONFIG.access.username.is_none()
+    {
+        tracing::warn!("broadcast is enabled without [access] rules, anyone on the network can use this service");
+    }
+
+    let mut servers = Vec::new();
+
+    if CONFIG.tls.enabled() {
+        match tls::ssl_config(&CONFIG.tls)
+            .and_then(|ssl| Server::https(CONFIG.tls_addr(), ssl).map_err(|e| e.to_string()))
+        {
+            Ok(server) => {
+                tracing::info!("launching service at https://{}/", CONFIG.tls_addr());
+                servers.push(server);
+            }
+            Err(e) => {
+                tracing::error!("failed to start HTTPS service: {e}");
+                return;
+            }
         }
-    };
+    } else if !CONFIG.tls.http {
+        tracing::warn!("tls.http is disabled but HTTPS isn't configured, serving HTTP anyway");
+    }
+
+    if CONFIG.tls.http || !CONFIG.tls.enabled() {
+        match Server::http(CONFIG.addr()) {
+            Ok(server) => {
+                tracing::info!("launching service at http://{}/", CONFIG.addr());
+                servers.push(server);
+            }
+            Err(e) => {
+                tracing::error!("failed to start service: {e}");
+                return;
+            }
+        }
+    }
 
-    for request in server.incoming_requests() {
+    let listeners: Vec<_> = servers
+        .into_iter()
+        .map(|server| thread::spawn(move || listen(server)))
+        .collect();
+
+    for listener in listeners {
+        let _ = listener.join();
+    }
+}
+
+fn listen(server: Server) {
+    for mut request in server.incoming_requests() {
         thread::spawn(move || {
-            let response = handle_request(&request);
+            let _active = metrics::ActiveThread::start();
+
+            let start = Instant::now();
+            let (route, response) = handle_request(&mut request);
+            metrics::record_request(route, response.status_code().0, start.elapsed());
 
             if let Err(e) = request.respond(response) {
                 tracing::error!("error handling request: {e}");
@@ -43,59 +202,764 @@ fn main() {
     }
 }
 
-fn handle_request(request: &Request) -> Response<Cursor<Vec<u8>>> {
-    if let Some(terms) = request.url().strip_prefix("/?q=") {
-        let redirect = parse_terms(terms);
+fn generate_certificate(args: &[String]) {
+    let force = args.iter().any(|arg| arg == "--force");
+    let dir = args
+        .iter()
+        .find(|arg| *arg != "--force")
+        .map_or_else(tls::default_directory, PathBuf::from);
+
+    match tls::generate_certificate(&dir, force) {
+        Ok((certificate, key)) => println!(
+            "Wrote a self-signed certificate for localhost, add this to your configuration file:\n\n\
+            [tls]\ncertificate = {certificate:?}\nkey = {key:?}\n\n\
+            Browsers will warn about it until it's trusted by your system or browser."
+        ),
+        Err(e) => {
+            eprintln!("failed to generate certificate: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+fn dump(args: &[String]) {
+    let fail = |message: String| -> ! {
+        eprintln!("{message}\n\n{USAGE}");
+        process::exit(2);
+    };
+
+    let Some(format) = args.first() else {
+        fail("dump needs a format".into());
+    };
+    let format: dump::Format = format.parse().unwrap_or_else(|e| fail(e));
+
+    let mut custom = false;
+    let mut output = None;
+
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--custom" => custom = true,
+            "--output" => {
+                output = Some(PathBuf::from(
+                    options
+                        .next()
+                        .unwrap_or_else(|| fail("--output needs a path".into())),
+                ))
+            }
+            other => fail(format!("unknown dump option '{other}'")),
+        }
+    }
+
+    let result = dump::dump(format, custom).and_then(|(text, hidden)| {
+        if hidden > 0 {
+            eprintln!("left out {hidden} custom shortcut(s) hidden by other engines");
+        }
 
-        return Response::new(
-            StatusCode(302),
-            vec![
-                Header::from_bytes("Location", redirect.as_bytes()).unwrap(),
-                Header::from_bytes("Cache-Control", "no-cache, no-store, must-revalidate").unwrap(),
-            ],
-            Cursor::new(Vec::with_capacity(0)),
-            Some(0),
-            None,
+        match output {
+            Some(path) => fs::write(&path, text)
+                .map(|_| eprintln!("dumped search engines to {}", path.display()))
+                .map_err(|e| e.to_string()),
+            None => {
+                print!("{text}");
+                Ok(())
+            }
+        }
+    });
+
+    if let Err(e) = result {
+        eprintln!("failed to dump: {e}");
+        process::exit(1);
+    }
+}
+
+fn export(args: &[String]) {
+    let fail = |message: String| -> ! {
+        eprintln!("{message}\n\n{USAGE}");
+        process::exit(2);
+    };
+
+    let Some(format) = args.first() else {
+        fail("export needs a format".into());
+    };
+    let format: export::Format = format.parse().unwrap_or_else(|e| fail(e));
+
+    let mut subset = export::Subset::Custom;
+    let mut filter = None;
+    let mut output = None;
+
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--builtin" => subset = export::Subset::Builtin,
+            "--all" => subset = export::Subset::All,
+            "--filter" => {
+                let pattern = options
+                    .next()
+                    .unwrap_or_else(|| fail("--filter needs a pattern".into()));
+                filter = Some(regex::Regex::new(pattern).unwrap_or_else(|e| fail(e.to_string())));
+            }
+            "--output" => {
+                output = Some(PathBuf::from(
+                    options
+                        .next()
+                        .unwrap_or_else(|| fail("--output needs a path".into())),
+                ))
+            }
+            other => fail(format!("unknown export option '{other}'")),
+        }
+    }
+
+    let (entries, groups) = export::entries(subset, filter.as_ref());
+
+    if groups > 0 {
+        eprintln!(
+            "left out {groups} shortcut group(s), browsers can't open several engines at once"
+        );
+    }
+
+    let result = match (format, output) {
+        (export::Format::OpenSearch, Some(dir)) => export::write_opensearch(&entries, &dir)
+            .map(|written| eprintln!("wrote {written} OpenSearch files to {}", dir.display())),
+        (export::Format::OpenSearch, None) => fail("opensearch needs an --output directory".into()),
+        (format, output) => {
+            let text = match format {
+                export::Format::Chromium => export::chromium(&entries),
+                _ => export::bookmarks(&entries),
+            };
+
+            match output {
+                Some(path) => fs::write(&path, text)
+                    .map(|_| {
+                        eprintln!("exported {} shortcuts to {}", entries.len(), path.display())
+                    })
+                    .map_err(|e| e.to_string()),
+                None => {
+                    print!("{text}");
+                    Ok(())
+                }
+            }
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("failed to export: {e}");
+        process::exit(1);
+    }
+}
+
+fn import(paths: &[String]) {
+    if paths.is_empty() {
+        eprintln!("import needs at least one file\n\n{USAGE}");
+        process::exit(2);
+    }
+
+    let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+
+    if let Err(e) = import::run(&paths) {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+}
+
+/// Read and validate a database written by `build.rs`, keeping it alive for the rest of the program.
+fn load_database(path: &Path) -> Result<&'static engines::ArchivedSearchEngineDatabase, String> {
+    let db = leak_database(&fs::read(path).map_err(|e| e.to_string())?)?;
+
+    // `Config` relies on the default engine being there, just like `build.rs` checks
+    let default = engines::default::engine();
+    if db.get_engine(&default).is_none() {
+        return Err(format!("default engine '{default}' not found"));
+    }
+
+    Ok(db)
+}
+
+fn leak_database(bytes: &[u8]) -> Result<&'static engines::ArchivedSearchEngineDatabase, String> {
+    let mut aligned = rkyv::util::AlignedVec::<16>::new();
+    aligned.extend_from_slice(bytes);
+
+    engines::ArchivedSearchEngineDatabase::from_bytes(Box::leak(Box::new(aligned)))
+        .map_err(|e| e.to_string())
+}
+
+/// Rebuild the database from a list of bang sources, where `builtin` stands for `base` and is
+/// put first, with the lowest precedence, if it isn't listed.
+fn with_sources(
+    base: &'static engines::ArchivedSearchEngineDatabase,
+    list: &str,
+) -> Result<&'static engines::ArchivedSearchEngineDatabase, String> {
+    let mut sources = bangs::parse_sources(list)?;
+    if !sources.contains(&bangs::Source::Builtin) {
+        sources.insert(0, bangs::Source::Builtin);
+    }
+
+    let mut db = engines::SearchEngineDatabase::default();
+
+    for source in sources {
+        let bangs::Source::File { format, path } = source else {
+            for shortcut in base.shortcuts() {
+                if let Some(engine) = base.get_engine(shortcut) {
+                    db.insert_template(
+                        &shortcut.into(),
+                        config::force_clone(&engine),
+                        base.get_template(shortcut).unwrap_or_default(),
+                    );
+                } else if let Some(group) = base.get_group(shortcut) {
+                    db.insert_group(
+                        &shortcut.into(),
+                        engines::SearchGroup {
+                            name: group.name.into(),
+                            members: group.members.into_iter().map(Into::into).collect(),
+                        },
+                    );
+                }
+            }
+            continue;
+        };
+
+        let read = bangs::read(format, &path)?;
+        tracing::info!(
+            "read {} bangs from {format}:{path:?}, skipped {}",
+            read.bangs.len(),
+            read.skipped.len()
         );
+
+        for bang in read.bangs {
+            for placeholder in &bang.unsupported {
+                tracing::warn!(
+                    "!{} uses the unsupported placeholder {placeholder}, leaving it as is",
+                    bang.shortcut
+                );
+            }
+
+            db.insert_template(
+                &bang.shortcut.into(),
+                config::OwnedSearchEngine {
+                    name: bang.name.into(),
+                    url: bang.url.into(),
+                    category: bang.category.map(Into::into),
+                    subcategory: bang.subcategory.map(Into::into),
+                },
+                bang.template,
+            );
+        }
     }
 
-    let (data, code) = match request.url() {
-        "/" => (info::INDEX.as_bytes(), 200),
-        "/info" => (info::INFO.as_bytes(), 200),
-        _ => (info::NOT_FOUND.as_bytes(), 404),
+    leak_database(&db.to_bytes().map_err(|e| e.to_string())?)
+}
+
+/// Largest accepted `POST /` body, far longer than any real search.
+const MAX_BODY_LEN: u64 = 16 * 1024;
+
+/// Respond to `request`, along with the route that handled it for the request metrics.
+fn handle_request(request: &mut Request) -> (&'static str, Response<Cursor<Vec<u8>>>) {
+    let cookie = match access::check(request) {
+        Ok(cookie) => cookie,
+        Err(denied) => return ("denied", reject(request, denied)),
     };
 
-    Response::from_data(data)
+    let (route, mut response) = route(request);
+    if let Some(cookie) = cookie {
+        response.add_header(cookie);
+    }
+    (route, response)
+}
+
+fn reject(request: &Request, denied: access::Denied) -> Response<Cursor<Vec<u8>>> {
+    tracing::warn!(
+        "rejected request from {}",
+        request
+            .remote_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_default()
+    );
+
+    match denied {
+        access::Denied::Forbidden => html_response(&info::FORBIDDEN, 403),
+        access::Denied::Unauthorized => html_response(&info::UNAUTHORIZED, 401).with_header(
+            Header::from_bytes("WWW-Authenticate", "Basic realm=\"Local Search Shortcuts\"")
+                .unwrap(),
+        ),
+    }
+}
+
+/// Respond to an allowed request, along with the route it matched, `search` for searches and
+/// `other` for anything not found.
+fn route(request: &mut Request) -> (&'static str, Response<Cursor<Vec<u8>>>) {
+    if let Some(terms) = search_terms(request.url()) {
+        return ("search", search(terms));
+    }
+
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    if request.method() == &Method::Post && CONFIG.search_paths.iter().any(|p| p == path) {
+        let response = match read_form(request) {
+            Ok(body) => match CONFIG
+                .query_params
+                .iter()
+                .find_map(|name| raw_param(&body, name))
+            {
+                Some(terms) => search(terms),
+                None => html_response(&info::BAD_REQUEST, 400),
+            },
+            Err((page, code)) => html_response(page, code),
+        };
+        return ("search", response);
+    }
+
+    if (request.method(), path) == (&Method::Post, "/history/clear") {
+        if !same_origin(request) {
+            tracing::warn!("rejected cross-site request to clear the history");
+            return ("/history/clear", html_response(&info::FORBIDDEN, 403));
+        }
+
+        history::clear();
+
+        let response = Response::from_data(Vec::with_capacity(0))
+            .with_status_code(StatusCode(303))
+            .with_header(Header::from_bytes("Location", "/history").unwrap());
+        return ("/history/clear", response);
+    }
+
+    // searches are private, so only the service's own pages may read them
+    if let Some(route) = ["/history", "/history.json", "/history.csv"]
+        .into_iter()
+        .find(|route| *route == path)
+    {
+        if !same_origin(request) {
+            tracing::warn!("rejected cross-site request for the history");
+            return (route, html_response(&info::FORBIDDEN, 403));
+        }
+    }
+
+    let (route, data, code, content_type) = match path {
+        "/" => ("/", info::index(), 200, "text/html"),
+        "/info" => ("/info", info::INFO.as_str().into(), 200, "text/html"),
+        "/stats" => ("/stats", stats::render_html(), 200, "text/html"),
+        "/stats.json" => ("/stats.json", stats::render_json(), 200, "application/json"),
+        "/history" => (
+            "/history",
+            history::render_html(&query_param(query, "q").unwrap_or_default()),
+            200,
+            "text/html",
+        ),
+        "/suggest" => (
+            "/suggest",
+            suggest::render_json(&query_param(query, "q").unwrap_or_default()),
+            200,
+            "application/x-suggestions+json",
+        ),
+        "/metrics" => (
+            "/metrics",
+            metrics::render(),
+            200,
+            "text/plain; version=0.0.4",
+        ),
+        "/history.json" => (
+            "/history.json",
+            history::render_json(),
+            200,
+            "application/json",
+        ),
+        "/history.csv" => ("/history.csv", history::render_csv(), 200, "text/csv"),
+        _ => ("other", info::NOT_FOUND.as_str().into(), 404, "text/html"),
+    };
+
+    let response = Response::from_string(data)
         .with_status_code(StatusCode(code))
-        .with_header(Header::from_bytes("Content-Type", "text/html").unwrap())
+        .with_header(Header::from_bytes("Content-Type", content_type).unwrap());
+    (route, response)
 }
 
-fn parse_terms(encoded_terms: &str) -> String {
-    let terms = urlencoding::decode(encoded_terms)
-        .expect("url not encoded as utf8 (impossible)")
-        .replace('+', " ");
+/// Whether a request changing or reading private state came from one of the service's own pages,
+/// going by the `Origin` or `Referer` browsers send with form submissions. Requests with neither
+/// don't come from a browser, so a page on another site can't have sent them. The `Host` has to
+/// name this machine too, or another site could send them from its own origin by pointing its
+/// domain here.
+fn same_origin(request: &Request) -> bool {
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.as_str())
+    };
 
-    let Some((shortcut, url)): Option<(&str, &str)> = terms
-        .split_whitespace()
-        .find(|s| s.starts_with('!'))
-        .and_then(|s| {
-            let trimmed = s.trim_start_matches('!');
+    if !header("Host").is_some_and(access::local_host) {
+        return false;
+    }
 
-            ENGINES
-                .get_engine(trimmed)
-                .or(CONFIG.engines.get_engine(trimmed))
-                .map(|e| (s, e.url))
-        })
-    else {
-        return CONFIG.default_engine.url.replace("{s}", encoded_terms);
+    let Some(source) = header("Origin").or_else(|| header("Referer")) else {
+        return true;
+    };
+
+    // `null` for sandboxed or privacy sensitive contexts has no host and never matches
+    let source_host = source
+        .split_once("://")
+        .map(|(_, rest)| rest.split(['/', '?', '#']).next().unwrap_or_default());
+
+    source_host.is_some_and(|source_host| {
+        header("Host").is_some_and(|host| host.eq_ignore_ascii_case(source_host))
+    })
+}
+
+fn html_response(page: &str, code: u16) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(page)
+        .with_status_code(StatusCode(code))
+        .with_header(Header::from_bytes("Content-Type", "text/html").unwrap())
+}
+
+/// Redirect to wherever `encoded_terms` (the raw `q` parameter) points.
+fn search(encoded_terms: &str) -> Response<Cursor<Vec<u8>>> {
+    let redirect = match parse_terms(encoded_terms) {
+        Ok(redirect) => redirect,
+        Err(err) => {
+            tracing::warn!("rejected search: {err}");
+            return html_response(&info::BAD_REQUEST, 400);
+        }
     };
 
-    if !url.contains("{s}") {
-        return url.to_string();
+    // a group's members can all be removed or misspelled, leaving nothing to open
+    if redirect.launch.as_ref().is_some_and(Vec::is_empty) {
+        tracing::warn!("group '{}' has no engines to launch", redirect.engine);
+        return html_response(&info::EMPTY_GROUP, 404);
     }
 
-    url.replace(
-        "{s}",
-        urlencoding::encode(terms.replace(shortcut, "").trim()).as_ref(),
+    if !redirect.private {
+        tracing::info!(
+            shortcut = redirect.shortcut.as_deref().unwrap_or_default(),
+            source = ?redirect.source,
+            "redirect"
+        );
+    }
+    metrics::record_redirect(&redirect);
+    stats::record(&redirect);
+    history::record(&redirect);
+
+    if let Some(launch) = &redirect.launch {
+        return html_response(&info::launcher(&redirect.engine, launch), 200).with_header(
+            Header::from_bytes("Cache-Control", "no-cache, no-store, must-revalidate").unwrap(),
+        );
+    }
+
+    Response::new(
+        StatusCode(302),
+        vec![
+            Header::from_bytes("Location", redirect.url.as_bytes()).unwrap(),
+            Header::from_bytes("Cache-Control", "no-cache, no-store, must-revalidate").unwrap(),
+        ],
+        Cursor::new(Vec::with_capacity(0)),
+        Some(0),
+        None,
     )
 }
+
+/// Read an `application/x-www-form-urlencoded` body of at most [`MAX_BODY_LEN`] bytes.
+fn read_form(request: &mut Request) -> Result<String, (&'static str, u16)> {
+    let is_form = request.headers().iter().any(|h| {
+        h.field.equiv("Content-Type")
+            && h.value
+                .as_str()
+                .to_ascii_lowercase()
+                .starts_with("application/x-www-form-urlencoded")
+    });
+
+    if !is_form {
+        return Err((&info::UNSUPPORTED_MEDIA_TYPE, 415));
+    }
+
+    if request
+        .body_length()
+        .is_some_and(|len| len as u64 > MAX_BODY_LEN)
+    {
+        return Err((&info::PAYLOAD_TOO_LARGE, 413));
+    }
+
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_LEN + 1)
+        .read_to_end(&mut body)
+        .map_err(|_| (info::BAD_REQUEST.as_str(), 400))?;
+
+    if body.len() as u64 > MAX_BODY_LEN {
+        return Err((&info::PAYLOAD_TOO_LARGE, 413));
+    }
+
+    String::from_utf8(body).map_err(|_| (info::BAD_REQUEST.as_str(), 400))
+}
+
+/// The raw search terms if `url` is a search, i.e. one of `search_paths` with one of `query_params`.
+///
+/// Any other parameters (`client=firefox`, `token`, ...) are deliberately ignored.
+fn search_terms(url: &str) -> Option<&str> {
+    let (path, query) = url.split_once('?')?;
+
+    if !CONFIG.search_paths.iter().any(|p| p == path) {
+        return None;
+    }
+
+    CONFIG
+        .query_params
+        .iter()
+        .find_map(|name| raw_param(query, name))
+}
+
+/// Find the first `name=value` pair in a query string, without decoding it.
+fn raw_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Find and decode the first `name=value` pair in a query string.
+fn query_param(query: &str, name: &str) -> Option<String> {
+    raw_param(query, name).and_then(|value| terms::decode(value).ok())
+}
+
+/// Which database the engine a search was sent to came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Builtin,
+    Custom,
+    Rule,
+    /// The query was an address and was opened directly.
+    Navigation,
+    Default,
+}
+
+#[derive(Debug)]
+pub struct Redirect {
+    pub url: String,
+    /// The matched shortcut, without the leading `!`.
+    pub shortcut: Option<String>,
+    pub source: Source,
+    /// Name of the engine the search was sent to.
+    pub engine: String,
+    /// The decoded search terms with the shortcut removed.
+    pub terms: String,
+    /// The query contained a lone `!`, so it shouldn't be recorded anywhere.
+    pub private: bool,
+    /// The query had a shortcut that didn't match any engine.
+    pub unknown_bang: bool,
+    /// Name and URL of every engine to open, when the shortcut was a group.
+    pub launch: Option<Vec<(String, String)>>,
+}
+
+fn parse_terms(encoded_terms: &str) -> Result<Redirect, terms::InvalidTerms> {
+    let mut terms = terms::decode(encoded_terms)?;
+
+    let mut private = false;
+    while let Some(token) = terms::tokenize(&terms).into_iter().find(|t| t.text == "!") {
+        terms = terms::remove_token(&terms, &token);
+        private = true;
+    }
+
+    let bang = terms::tokenize(&terms)
+        .into_iter()
+        .find(terms::Token::is_bang);
+
+    // engines take precedence over groups with the same shortcut
+    let group = bang
+        .filter(|token| find_engine(token.text.trim_start_matches('!')).is_none())
+        .and_then(|token| {
+            find_group(token.text.trim_start_matches('!'))
+                .map(|(group, source)| (token, group, source))
+        });
+
+    if let Some((token, group, source)) = group {
+        let remaining = terms::remove_token(&terms, &token);
+
+        let launch: Vec<(String, String)> = group
+            .members
+            .iter()
+            .filter_map(|member| find_engine(member).map(|found| (member, found)))
+            .map(|(member, (engine, source))| {
+                (
+                    engine.name.to_string(),
+                    search_url(member, &engine, source, &remaining),
+                )
+            })
+            .collect();
+
+        return Ok(Redirect {
+            url: launch
+                .first()
+                .map(|(_, url)| url.clone())
+                .unwrap_or_default(),
+            shortcut: Some(token.text.trim_start_matches('!').to_string()),
+            source,
+            engine: group.name.to_string(),
+            terms: remaining,
+            private,
+            unknown_bang: false,
+            launch: Some(launch),
+        });
+    }
+
+    let Some((token, engine, source)) = bang.and_then(|token| {
+        find_engine(token.text.trim_start_matches('!'))
+            .map(|(engine, source)| (token, engine, source))
+    }) else {
+        // `!site:<domain>` searches the default engine within that domain
+        let site = bang.and_then(|token| {
+            let site = token.text.trim_start_matches('!').strip_prefix("site:")?;
+            (!site.is_empty()).then_some((token, site))
+        });
+
+        if let Some((token, site)) = site {
+            let terms = format!("site:{site} {}", terms::remove_token(&terms, &token));
+            let terms = terms.trim_end();
+
+            return Ok(Redirect {
+                url: engine_url(&CONFIG.default_engine.url, terms),
+                shortcut: None,
+                source: Source::Default,
+                engine: CONFIG.default_engine.name.to_string(),
+                terms: terms.to_string(),
+                private,
+                unknown_bang: false,
+                launch: None,
+            });
+        }
+
+        let terms = terms.trim();
+
+        // a leading `?` forces a search with the default engine, like in browsers' address bars
+        let (terms, forced) = match terms.strip_prefix('?') {
+            Some(rest) => (rest.trim_start(), true),
+            None => (terms, false),
+        };
+
+        let navigation = (!forced && bang.is_none() && CONFIG.navigation.enabled)
+            .then(|| navigate::url(terms, &CONFIG.navigation.scheme))
+            .flatten();
+
+        if let Some(url) = navigation {
+            return Ok(Redirect {
+                url,
+                shortcut: None,
+                source: Source::Navigation,
+                engine: "Direct navigation".to_string(),
+                terms: terms.to_string(),
+                private,
+                unknown_bang: false,
+                launch: None,
+            });
+        }
+
+        let rule = (!forced)
+            .then(|| CONFIG.rules.iter().find_map(|rule| rule.apply(terms)))
+            .flatten();
+
+        if let Some((url, engine)) = rule {
+            return Ok(Redirect {
+                url,
+                shortcut: None,
+                source: Source::Rule,
+                engine,
+                terms: terms.to_string(),
+                private,
+                unknown_bang: bang.is_some(),
+                launch: None,
+            });
+        }
+
+        return Ok(Redirect {
+            url: engine_url(&CONFIG.default_engine.url, terms),
+            shortcut: None,
+            source: Source::Default,
+            engine: CONFIG.default_engine.name.to_string(),
+            terms: terms.to_string(),
+            private,
+            unknown_bang: bang.is_some(),
+            launch: None,
+        });
+    };
+
+    let remaining = terms::remove_token(&terms, &token);
+    let shortcut = token.text.trim_start_matches('!');
+
+    Ok(Redirect {
+        url: search_url(shortcut, &engine, source, &remaining),
+        shortcut: Some(shortcut.to_string()),
+        source,
+        engine: engine.name.to_string(),
+        terms: remaining,
+        private,
+        unknown_bang: false,
+        launch: None,
+    })
+}
+
+/// Look up an engine by shortcut, built-in engines first.
+fn find_engine(shortcut: &str) -> Option<(engines::SearchEngineRef<'static>, Source)> {
+    ENGINES
+        .get_engine(shortcut)
+        .map(|e| (e, Source::Builtin))
+        .or(CONFIG
+            .engines
+            .get_engine(shortcut)
+            .map(|e| (e, Source::Custom)))
+}
+
+/// Where a search for `terms` with `engine`, found for `shortcut` in `source`, goes, upgraded to
+/// HTTPS if it's a built-in engine and `[https]` says so.
+fn search_url(
+    shortcut: &str,
+    engine: &engines::SearchEngineRef,
+    source: Source,
+    terms: &str,
+) -> String {
+    fill_template(shortcut, engine, source, |template| render(template, terms))
+}
+
+/// The URL [`search_url`] fills in, with `{s}` left for the search terms.
+fn search_template(shortcut: &str, engine: &engines::SearchEngineRef, source: Source) -> String {
+    fill_template(shortcut, engine, source, engines::Segment::to_url)
+}
+
+fn fill_template(
+    shortcut: &str,
+    engine: &engines::SearchEngineRef,
+    source: Source,
+    fill: impl Fn(&[engines::Segment]) -> String,
+) -> String {
+    let template = match source {
+        Source::Builtin => ENGINES.get_template(shortcut),
+        _ => CONFIG.engines.get_template(shortcut),
+    };
+
+    // every engine is stored with a template, but don't redirect nowhere if one is missing
+    let url = fill(&template.unwrap_or_else(|| engines::Segment::parse(engine.url)));
+
+    match source {
+        Source::Builtin => CONFIG.https.url(shortcut, &url).into_owned(),
+        _ => url,
+    }
+}
+
+/// Look up a shortcut group, built-in groups first.
+fn find_group(shortcut: &str) -> Option<(engines::SearchGroupRef<'static>, Source)> {
+    ENGINES
+        .get_group(shortcut)
+        .map(|g| (g, Source::Builtin))
+        .or(CONFIG
+            .engines
+            .get_group(shortcut)
+            .map(|g| (g, Source::Custom)))
+}
+
+/// Substitute the search terms into an engine's URL template.
+fn engine_url(template: &str, terms: &str) -> String {
+    render(&engines::Segment::parse(template), terms)
+}
+
+/// Fill in a parsed template, encoding `terms` for wherever they go.
+fn render(template: &[engines::Segment], terms: &str) -> String {
+    engines::Segment::render(template, |position| terms::encode_at(terms, position))
+}
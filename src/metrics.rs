@@ -0,0 +1,183 @@
+//! Counters exposed at `/metrics` in the Prometheus text format.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering::Relaxed},
+        Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use crate::{Redirect, Source};
+
+/// Upper bounds of the `handle_request` latency histogram, in seconds.
+const LATENCY_BUCKETS: [f64; 10] = [
+    0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5,
+];
+
+static REQUESTS: Mutex<BTreeMap<(&str, u16), u64>> = Mutex::new(BTreeMap::new());
+
+static BUILTIN_REDIRECTS: AtomicU64 = AtomicU64::new(0);
+static CUSTOM_REDIRECTS: AtomicU64 = AtomicU64::new(0);
+static RULE_REDIRECTS: AtomicU64 = AtomicU64::new(0);
+static NAVIGATION_REDIRECTS: AtomicU64 = AtomicU64::new(0);
+static DEFAULT_REDIRECTS: AtomicU64 = AtomicU64::new(0);
+static UNKNOWN_BANGS: AtomicU64 = AtomicU64::new(0);
+
+static LATENCY_COUNTS: [AtomicU64; LATENCY_BUCKETS.len()] =
+    [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len()];
+static LATENCY_COUNT: AtomicU64 = AtomicU64::new(0);
+static LATENCY_SUM_NANOS: AtomicU64 = AtomicU64::new(0);
+
+static ACTIVE_THREADS: AtomicI64 = AtomicI64::new(0);
+
+/// Config files read when the service starts, it doesn't reload them.
+static CONFIG_FILES_LOADED: AtomicU64 = AtomicU64::new(0);
+static CONFIG_FILES_FAILED: AtomicU64 = AtomicU64::new(0);
+
+/// Counts a request thread as active until dropped.
+pub struct ActiveThread(());
+
+impl ActiveThread {
+    pub fn start() -> Self {
+        ACTIVE_THREADS.fetch_add(1, Relaxed);
+        Self(())
+    }
+}
+
+impl Drop for ActiveThread {
+    fn drop(&mut self) {
+        ACTIVE_THREADS.fetch_sub(1, Relaxed);
+    }
+}
+
+/// Count a request handled by `route`, as told by the router rather than the raw path so that
+/// searches are counted as `search` however they were sent.
+pub fn record_request(route: &'static str, status: u16, elapsed: Duration) {
+    *REQUESTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry((route, status))
+        .or_default() += 1;
+
+    let secs = elapsed.as_secs_f64();
+    for (bound, count) in LATENCY_BUCKETS.iter().zip(&LATENCY_COUNTS) {
+        if secs <= *bound {
+            count.fetch_add(1, Relaxed);
+        }
+    }
+    LATENCY_COUNT.fetch_add(1, Relaxed);
+    LATENCY_SUM_NANOS.fetch_add(elapsed.as_nanos() as u64, Relaxed);
+}
+
+pub fn record_redirect(redirect: &Redirect) {
+    match redirect.source {
+        Source::Builtin => &BUILTIN_REDIRECTS,
+        Source::Custom => &CUSTOM_REDIRECTS,
+        Source::Rule => &RULE_REDIRECTS,
+        Source::Navigation => &NAVIGATION_REDIRECTS,
+        Source::Default => &DEFAULT_REDIRECTS,
+    }
+    .fetch_add(1, Relaxed);
+
+    if redirect.unknown_bang {
+        UNKNOWN_BANGS.fetch_add(1, Relaxed);
+    }
+}
+
+pub fn record_config_file(success: bool) {
+    if success {
+        CONFIG_FILES_LOADED.fetch_add(1, Relaxed);
+    } else {
+        CONFIG_FILES_FAILED.fetch_add(1, Relaxed);
+    }
+}
+
+pub fn render() -> String {
+    let mut output = String::new();
+
+    output.push_str("# HELP lss_requests_total HTTP requests handled, by route and status.\n");
+    output.push_str("# TYPE lss_requests_total counter\n");
+    for ((route, status), count) in REQUESTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+    {
+        let _ = writeln!(
+            output,
+            "lss_requests_total{{route=\"{route}\",status=\"{status}\"}} {count}"
+        );
+    }
+
+    output.push_str("# HELP lss_redirects_total Search redirects, by engine source.\n");
+    output.push_str("# TYPE lss_redirects_total counter\n");
+    for (source, count) in [
+        ("builtin", &BUILTIN_REDIRECTS),
+        ("custom", &CUSTOM_REDIRECTS),
+        ("rule", &RULE_REDIRECTS),
+        ("navigation", &NAVIGATION_REDIRECTS),
+        ("default", &DEFAULT_REDIRECTS),
+    ] {
+        let _ = writeln!(
+            output,
+            "lss_redirects_total{{source=\"{source}\"}} {}",
+            count.load(Relaxed)
+        );
+    }
+
+    output.push_str("# HELP lss_unknown_bangs_total Searches whose shortcut matched no engine.\n");
+    output.push_str("# TYPE lss_unknown_bangs_total counter\n");
+    let _ = writeln!(
+        output,
+        "lss_unknown_bangs_total {}",
+        UNKNOWN_BANGS.load(Relaxed)
+    );
+
+    output.push_str("# HELP lss_request_duration_seconds Time spent handling a request.\n");
+    output.push_str("# TYPE lss_request_duration_seconds histogram\n");
+    for (bound, count) in LATENCY_BUCKETS.iter().zip(&LATENCY_COUNTS) {
+        let _ = writeln!(
+            output,
+            "lss_request_duration_seconds_bucket{{le=\"{bound}\"}} {}",
+            count.load(Relaxed)
+        );
+    }
+    let count = LATENCY_COUNT.load(Relaxed);
+    let _ = writeln!(
+        output,
+        "lss_request_duration_seconds_bucket{{le=\"+Inf\"}} {count}"
+    );
+    let _ = writeln!(
+        output,
+        "lss_request_duration_seconds_sum {}",
+        LATENCY_SUM_NANOS.load(Relaxed) as f64 / 1e9
+    );
+    let _ = writeln!(output, "lss_request_duration_seconds_count {count}");
+
+    output.push_str("# HELP lss_active_threads Requests currently being handled.\n");
+    output.push_str("# TYPE lss_active_threads gauge\n");
+    let _ = writeln!(
+        output,
+        "lss_active_threads {}",
+        ACTIVE_THREADS.load(Relaxed)
+    );
+
+    output.push_str(
+        "# HELP lss_config_files_loaded_total Configuration files read at startup, by result.\n",
+    );
+    output.push_str("# TYPE lss_config_files_loaded_total counter\n");
+    let _ = writeln!(
+        output,
+        "lss_config_files_loaded_total{{result=\"success\"}} {}",
+        CONFIG_FILES_LOADED.load(Relaxed)
+    );
+    let _ = writeln!(
+        output,
+        "lss_config_files_loaded_total{{result=\"failure\"}} {}",
+        CONFIG_FILES_FAILED.load(Relaxed)
+    );
+
+    output
+}
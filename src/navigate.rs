@@ -0,0 +1,182 @@
+//! Detects queries that are addresses rather than searches, like `docs.rs/serde` or `localhost:3000`.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// Top level domains a bare `name.tld` is navigated to, every other one is searched for.
+const TLDS: &str = "\
+    com org net edu gov mil int info biz name pro mobi aero coop museum travel jobs \
+    app dev io ai co me tv cc ly gg sh so to fm am xyz site online tech store blog page \
+    cloud wiki news live shop art design email link club top run fun moe zip mov sucks \
+    ac ad ae af ag al ao aq ar as at au aw ax az ba bb bd be bf bg bh bi bj bm bn bo br \
+    bs bt bw by bz ca cd cf cg ch ci ck cl cm cn cr cu cv cw cx cy cz de dj dk dm do dz \
+    ec ee eg er es et eu fi fj fk fo fr ga gd ge gf gh gi gl gm gn gp gq gr gs gt gu gw \
+    gy hk hm hn hr ht hu id ie il im in iq ir is it je jm jo jp ke kg kh ki km kn kp kr \
+    kw ky kz la lb lc li lk lr ls lt lu lv ma mc md mg mh mk ml mm mn mo mp mq mr ms mt \
+    mu mv mw mx my mz na nc ne nf ng ni nl no np nr nu nz om pa pe pf pg ph pk pl pm pn \
+    pr ps pt pw py qa re ro rs ru rw sa sb sc sd se sg si sk sl sm sn sr ss st su sv sx \
+    sy sz tc td tf tg th tj tk tl tm tn tr tt tw tz ua ug uk us uy uz va vc ve vg vi vn \
+    vu wf ws ye yt za zm zw";
+
+/// Top level domains that are also common file extensions, like `build.rs` or `setup.py`, only
+/// navigated to when followed by a path.
+const FILE_EXTENSIONS: &str = "ac am cc cr in md mk ml mov pl pm ps py rs sh so sv zip";
+
+/// The URL to navigate to if `query` looks like an address, using `scheme` if it has none.
+pub fn url(query: &str, scheme: &str) -> Option<String> {
+    if query.is_empty() || !query.is_ascii() || query.contains(char::is_whitespace) {
+        return None;
+    }
+
+    if has_scheme(query) {
+        return Some(query.to_string());
+    }
+
+    let authority = query.split(['/', '?', '#']).next()?;
+
+    // more likely an email address than a login
+    if authority.contains('@') {
+        return None;
+    }
+
+    if is_file_name(authority) && authority.len() == query.len() {
+        return None;
+    }
+
+    is_address(authority).then(|| format!("{scheme}://{query}"))
+}
+
+/// A bare `name.ext` whose extension is in [`FILE_EXTENSIONS`].
+fn is_file_name(authority: &str) -> bool {
+    authority.rsplit_once('.').is_some_and(|(_, ext)| {
+        FILE_EXTENSIONS
+            .split_whitespace()
+            .any(|e| e.eq_ignore_ascii_case(ext))
+    })
+}
+
+fn has_scheme(query: &str) -> bool {
+    query.split_once("://").is_some_and(|(scheme, rest)| {
+        !rest.is_empty()
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+    })
+}
+
+fn is_address(authority: &str) -> bool {
+    if let Some(rest) = authority.strip_prefix('[') {
+        return rest.split_once(']').is_some_and(|(ip, port)| {
+            ip.parse::<Ipv6Addr>().is_ok()
+                && (port.is_empty() || port.strip_prefix(':').is_some_and(is_port))
+        });
+    }
+
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if is_port(port) => (host, Some(port)),
+        Some(_) => return false,
+        None => (authority, None),
+    };
+
+    // any `host:port`, as long as the host isn't just a number like in "16:9"
+    let host_port =
+        port.is_some() && host.starts_with(|c: char| c.is_ascii_alphabetic()) && is_hostname(host);
+
+    host_port
+        || host.eq_ignore_ascii_case("localhost")
+        || host.parse::<Ipv4Addr>().is_ok()
+        || is_domain(host)
+}
+
+fn is_port(port: &str) -> bool {
+    !port.is_empty()
+        && port.bytes().all(|b| b.is_ascii_digit())
+        && port.parse::<u16>().is_ok_and(|p| p != 0)
+}
+
+fn is_label(label: &str) -> bool {
+    !label.is_empty()
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+fn is_hostname(host: &str) -> bool {
+    host.split('.').all(is_label)
+}
+
+fn is_domain(host: &str) -> bool {
+    let host = host.strip_suffix('.').unwrap_or(host);
+
+    let Some((name, tld)) = host.rsplit_once('.') else {
+        return false;
+    };
+
+    is_hostname(name) && TLDS.split_whitespace().any(|t| t.eq_ignore_ascii_case(tld))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses() {
+        for (query, expected) in [
+            ("docs.rs/serde", "https://docs.rs/serde"),
+            ("example.com", "https://example.com"),
+            ("example.com.", "https://example.com."),
+            ("localhost", "https://localhost"),
+            ("localhost:3000", "https://localhost:3000"),
+            ("myhost:8080/path", "https://myhost:8080/path"),
+            ("192.168.1.1", "https://192.168.1.1"),
+            ("[::1]:8080", "https://[::1]:8080"),
+            ("http://example.org/a", "http://example.org/a"),
+            ("ftp://files.example.org", "ftp://files.example.org"),
+            ("crates.io?q=serde", "https://crates.io?q=serde"),
+        ] {
+            assert_eq!(url(query, "https").as_deref(), Some(expected), "{query}");
+        }
+    }
+
+    #[test]
+    fn searches() {
+        for query in [
+            "",
+            "hello world",
+            "rust",
+            "build.rs",
+            "setup.py",
+            "readme.md",
+            "install.sh",
+            "script.pl",
+            "archive.zip",
+            "16:9",
+            "user@example.com",
+            "foo.notatld",
+            "café.fr",
+            "-bad-.com",
+            "localhost:0",
+            "localhost:99999",
+        ] {
+            assert_eq!(url(query, "https"), None, "{query}");
+        }
+    }
+
+    #[test]
+    fn file_extension_with_path() {
+        assert_eq!(
+            url("example.rs/docs", "https").as_deref(),
+            Some("https://example.rs/docs")
+        );
+    }
+
+    #[test]
+    fn scheme() {
+        assert_eq!(
+            url("localhost:3000", "http").as_deref(),
+            Some("http://localhost:3000")
+        );
+    }
+}
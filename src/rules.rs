@@ -0,0 +1,163 @@
+//! Routes bang-less queries matching a pattern to a specific engine before falling back to the default.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{
+    engines::{Position, Segment},
+    terms,
+};
+
+/// A capture group placeholder in a rule's URL, like `{1}`.
+static GROUP: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{(\d+)\}").unwrap());
+
+#[derive(Debug)]
+pub struct Rule {
+    pub pattern: Regex,
+    pub target: Target,
+}
+
+#[derive(Debug)]
+pub enum Target {
+    /// An engine, looked up by shortcut when the config is loaded, with its URL template already
+    /// upgraded to HTTPS if `[https]` says so.
+    Engine {
+        name: String,
+        template: Vec<Segment>,
+    },
+    /// A URL template where `{s}` is the whole query and `{1}`, `{2}`, ... are capture groups.
+    Url { name: String, url: String },
+}
+
+impl Rule {
+    /// The URL `terms` redirects to and the name of where it goes, if the rule matches.
+    pub fn apply(&self, terms: &str) -> Option<(String, String)> {
+        let captures = self.pattern.captures(terms)?;
+
+        let (template, name) = match &self.target {
+            Target::Engine { name, template } => (template.clone(), name),
+            Target::Url { name, url } => {
+                let mut filled = String::new();
+                let mut rest = 0;
+
+                for placeholder in GROUP.captures_iter(url) {
+                    let whole = placeholder.get(0).unwrap();
+                    filled.push_str(&url[rest..whole.start()]);
+                    rest = whole.end();
+
+                    match placeholder[1].parse() {
+                        Ok(idx @ 1..) if idx < captures.len() => {
+                            let group = captures.get(idx).map_or("", |m| m.as_str());
+                            let encoded = terms::encode_at(group, Position::after(&filled));
+                            filled.push_str(&encoded);
+                        }
+                        _ => filled.push_str(whole.as_str()),
+                    }
+                }
+                filled.push_str(&url[rest..]);
+
+                (Segment::parse(&filled), name)
+            }
+        };
+
+        Some((crate::render(&template, terms), name.clone()))
+    }
+
+    pub fn target_name(&self) -> &str {
+        match &self.target {
+            Target::Engine { name, .. } | Target::Url { name, .. } => name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, target: Target) -> Rule {
+        Rule {
+            pattern: Regex::new(pattern).unwrap(),
+            target,
+        }
+    }
+
+    fn url(url: &str) -> Target {
+        Target::Url {
+            name: "Rule".into(),
+            url: url.into(),
+        }
+    }
+
+    #[test]
+    fn first_match_wins() {
+        let rules = [
+            rule(r"^[A-Z]+-\d+$", url("https://jira.example.com/browse/{s}")),
+            rule(r"^\w+-\w+$", url("https://example.org/?q={s}")),
+        ];
+        let apply = |terms| rules.iter().find_map(|rule| rule.apply(terms)).unwrap().0;
+
+        assert_eq!(apply("ABC-123"), "https://jira.example.com/browse/ABC-123");
+        assert_eq!(apply("abc-123"), "https://example.org/?q=abc-123");
+        assert!(rules.iter().all(|rule| rule.apply("abc 123").is_none()));
+    }
+
+    #[test]
+    fn capture_groups() {
+        let rule = rule(
+            r"^(?<project>[a-z]+)#(\d+)(?: (.+))?$",
+            url("https://example.org/{1}/issues/{2}?comment={3}&raw={s}&missing={4}"),
+        );
+
+        assert_eq!(
+            rule.apply("web#12").unwrap().0,
+            "https://example.org/web/issues/12?comment=&raw=web%2312&missing={4}"
+        );
+        assert_eq!(
+            rule.apply("web#12 a&b").unwrap().0,
+            "https://example.org/web/issues/12?comment=a%26b&raw=web%2312%20a%26b&missing={4}"
+        );
+        // the whole query isn't a capture group, and can't hide a placeholder in a group either
+        assert_eq!(
+            rule.apply("web#1 {s}").unwrap().0,
+            "https://example.org/web/issues/1?comment=%7Bs%7D&raw=web%231%20%7Bs%7D&missing={4}"
+        );
+    }
+
+    #[test]
+    fn encoded_by_position() {
+        let rule = rule(r"^(\S+) (\S+)$", url("https://{1}.example.org/{2}"));
+        assert_eq!(
+            rule.apply("bücher ..").unwrap().0,
+            "https://xn--bcher-kva.example.org/%2E%2E"
+        );
+    }
+
+    #[test]
+    fn engine_target() {
+        let rule = rule(
+            r"^[a-z]+$",
+            Target::Engine {
+                name: "Wikipedia".into(),
+                template: vec![
+                    Segment::Literal("https://".into()),
+                    Segment::Terms,
+                    Segment::Literal(".example.org/?ie=".into()),
+                    Segment::InputEncoding,
+                    Segment::Literal("&q=".into()),
+                    Segment::Terms,
+                ],
+            },
+        );
+
+        assert_eq!(
+            rule.apply("abc").unwrap(),
+            (
+                "https://abc.example.org/?ie=UTF-8&q=abc".into(),
+                "Wikipedia".into()
+            )
+        );
+        assert_eq!(rule.target_name(), "Wikipedia");
+        assert!(rule.apply("ABC").is_none());
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs,
+    path::PathBuf,
+    sync::{LazyLock, Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    frecency,
+    info::{base_html, escape_html, format_timestamp},
+    Redirect, Source, CONFIG, ENGINES,
+};
+
+pub static STATS_PATH: LazyLock<Option<PathBuf>> =
+    LazyLock::new(|| dirs::data_dir().map(|dir| dir.join("local-search-shortcuts/stats.json")));
+
+static STATS: LazyLock<Mutex<Stats>> = LazyLock::new(|| Mutex::new(load()));
+
+/// How many recent queries are kept per shortcut when `stats.queries` is enabled.
+const MAX_QUERIES: usize = 20;
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Stats {
+    #[serde(default)]
+    shortcuts: BTreeMap<String, Usage>,
+    #[serde(default)]
+    default_engine: Usage,
+    /// Searches sent by fallback rules, by the name of where they went.
+    #[serde(default)]
+    rules: BTreeMap<String, Usage>,
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+struct Usage {
+    count: u64,
+    last_used: u64,
+    /// Every use aged to `last_used` by [`frecency`]. Statistics written before this was kept
+    /// count every use as if it were the last.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frecency: Option<f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    queries: Vec<String>,
+}
+
+/// A row of the `/stats` page.
+#[derive(serde::Serialize)]
+struct Report {
+    shortcut: Option<String>,
+    engine: Option<String>,
+    custom: bool,
+    /// Counts searches a fallback rule sent to `engine`.
+    rule: bool,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+impl Usage {
+    fn score(&self) -> f64 {
+        self.frecency.unwrap_or(self.count as f64)
+    }
+}
+
+pub fn record(redirect: &Redirect) {
+    if !CONFIG.stats.enabled || redirect.private {
+        return;
+    }
+
+    let mut stats = STATS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let usage = match (&redirect.shortcut, redirect.source) {
+        (Some(shortcut), Source::Builtin | Source::Custom) => {
+            stats.shortcuts.entry(shortcut.to_lowercase()).or_default()
+        }
+        (_, Source::Default) => &mut stats.default_engine,
+        (_, Source::Rule) => stats.rules.entry(redirect.engine.clone()).or_default(),
+        // direct navigation isn't a search
+        _ => return,
+    };
+
+    let now = now();
+    usage.frecency = Some(frecency::add_use(usage.score(), usage.last_used, now));
+    usage.count += 1;
+    usage.last_used = now;
+
+    if CONFIG.stats.queries && !redirect.terms.is_empty() {
+        if usage.queries.len() >= MAX_QUERIES {
+            usage.queries.remove(0);
+        }
+        usage.queries.push(redirect.terms.clone());
+    }
+
+    if let Err(err) = save(&stats) {
+        tracing::warn!("failed to save usage statistics: {err}");
+    }
+}
+
+fn load() -> Stats {
+    let Some(path) = STATS_PATH.as_ref().filter(|path| path.exists()) else {
+        return Stats::default();
+    };
+
+    match fs::read(path)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+    {
+        Ok(stats) => stats,
+        Err(err) => {
+            tracing::warn!("failed to read usage statistics {path:?}, starting over: {err}");
+            Stats::default()
+        }
+    }
+}
+
+fn save(stats: &Stats) -> Result<(), String> {
+    let path = STATS_PATH.as_ref().ok_or("no data directory available")?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    // write then rename so a crash never leaves a half-written file behind
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(stats).map_err(|e| e.to_string())?)
+        .map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Use count, last use time and [`frecency`] score as of then of every shortcut that has been used.
+pub fn shortcut_usage() -> Vec<(String, u64, u64, f64)> {
+    if !CONFIG.stats.enabled {
+        return Vec::new();
+    }
+
+    let stats = STATS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    stats
+        .shortcuts
+        .iter()
+        .map(|(shortcut, usage)| {
+            (
+                shortcut.clone(),
+                usage.count,
+                usage.last_used,
+                usage.score(),
+            )
+        })
+        .collect()
+}
+
+/// Every used shortcut, most used first, followed by the default engine.
+fn reports() -> Vec<Report> {
+    let stats = STATS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let mut reports: Vec<Report> = stats
+        .shortcuts
+        .iter()
+        .map(|(shortcut, usage)| {
+            let builtin = ENGINES.get_engine(shortcut);
+            let custom = CONFIG.engines.get_engine(shortcut);
+
+            Report {
+                shortcut: Some(shortcut.clone()),
+                custom: builtin.is_none() && custom.is_some(),
+                engine: builtin.or(custom).map(|e| e.name.to_string()),
+                rule: false,
+                usage: usage.clone(),
+            }
+        })
+        .chain(stats.rules.iter().map(|(engine, usage)| Report {
+            shortcut: None,
+            engine: Some(engine.clone()),
+            custom: false,
+            rule: true,
+            usage: usage.clone(),
+        }))
+        .collect();
+
+    reports.sort_by_key(|r| std::cmp::Reverse(r.usage.count));
+
+    reports.push(Report {
+        shortcut: None,
+        engine: Some(CONFIG.default_engine.name.to_string()),
+        custom: false,
+        rule: false,
+        usage: stats.default_engine.clone(),
+    });
+
+    reports
+}
+
+pub fn render_json() -> String {
+    #[derive(serde::Serialize)]
+    struct Output {
+        enabled: bool,
+        queries: bool,
+        usage: Vec<Report>,
+    }
+
+    serde_json::to_string(&Output {
+        enabled: CONFIG.stats.enabled,
+        queries: CONFIG.stats.queries,
+        usage: if CONFIG.stats.enabled {
+            reports()
+        } else {
+            Vec::new()
+        },
+    })
+    .unwrap_or_default()
+}
+
+pub fn render_html() -> String {
+    let mut output = String::from(
+        r#"
+        <p><i><a href="/">Back to Main Page</a> - <a href="/stats.json">View as JSON</a></i></p>
+        <hr>
+        <h2>Usage Statistics</h2>
+    "#,
+    );
+
+    if !CONFIG.stats.enabled {
+        output.push_str(
+            "<p>Usage statistics are disabled. Set <code>enabled = true</code> in the <code>[stats]</code> section of your configuration file to start counting.</p>",
+        );
+        return base_html(&output);
+    }
+
+    if let Some(path) = STATS_PATH.as_ref() {
+        let _ = write!(output, "<p>Stored in <code>{path:?}</code></p>");
+    }
+
+    output.push_str(
+        r#"<table style="width: 100%; text-align: left;"><tr><th>Shortcut</th><th>Engine</th><th>Uses</th><th>Last Used</th></tr>"#,
+    );
+
+    let reports = reports();
+
+    for report in &reports {
+        let shortcut = report
+            .shortcut
+            .as_ref()
+            .map(|s| format!("!{}", escape_html(s)))
+            .unwrap_or(if report.rule {
+                "<i>Rule</i>".into()
+            } else {
+                "<i>Default Engine</i>".into()
+            });
+        let engine = report
+            .engine
+            .as_deref()
+            .map(escape_html)
+            .unwrap_or("<i>Unknown</i>".into());
+        let custom = if report.custom { " (Custom)" } else { "" };
+        let last_used = if report.usage.count != 0 {
+            format_timestamp(report.usage.last_used)
+        } else {
+            "Never".into()
+        };
+
+        let _ = write!(
+            output,
+            "<tr><td>{shortcut}</td><td>{engine}{custom}</td><td>{}</td><td>{last_used}</td></tr>",
+            report.usage.count
+        );
+
+        if !report.usage.queries.is_empty() {
+            let queries = report
+                .usage
+                .queries
+                .iter()
+                .map(|q| escape_html(q))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = write!(
+                output,
+                "<tr><td></td><td colspan=\"3\"><small>{queries}</small></td></tr>"
+            );
+        }
+    }
+
+    output.push_str("</table>");
+
+    let unused = CONFIG
+        .engines
+        .engines()
+        .flat_map(|(shortcuts, _)| shortcuts)
+        .filter(|s| *s != "info")
+        .filter(|s| !reports.iter().any(|r| r.shortcut.as_deref() == Some(*s)))
+        .fold(String::new(), |mut acc, s| {
+            let _ = write!(acc, "<li>!{}</li>", escape_html(s));
+            acc
+        });
+
+    if !unused.is_empty() {
+        let _ = write!(output, "<h3>Unused Custom Shortcuts</h3><ul>{unused}</ul>");
+    }
+
+    base_html(&output)
+}
@@ -0,0 +1,45 @@
+//! OpenSearch suggestions (`application/x-suggestions+json`) for completing shortcuts.
+
+use crate::{frecency, CONFIG, ENGINES};
+
+const MAX_SUGGESTIONS: usize = 10;
+
+/// Complete the shortcut being typed at the end of `query`, most frecent first.
+pub fn render_json(query: &str) -> String {
+    serde_json::to_string(&(query, suggestions(query))).unwrap_or_default()
+}
+
+fn suggestions(query: &str) -> Vec<String> {
+    let (before, last) = query.rsplit_once(' ').unwrap_or(("", query));
+
+    let Some(prefix) = last.strip_prefix('!').map(str::to_lowercase) else {
+        return Vec::new();
+    };
+
+    let scores = frecency::scores();
+
+    let mut shortcuts: Vec<&str> = ENGINES
+        .shortcuts()
+        .chain(CONFIG.engines.shortcuts())
+        .filter(|s| s.starts_with(&prefix))
+        .collect();
+
+    shortcuts.sort_by(|a, b| {
+        frecency::compare(&scores, a, b)
+            .then(a.len().cmp(&b.len()))
+            .then(a.cmp(b))
+    });
+    shortcuts.dedup();
+
+    shortcuts
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|s| {
+            if before.is_empty() {
+                format!("!{s}")
+            } else {
+                format!("{before} !{s}")
+            }
+        })
+        .collect()
+}
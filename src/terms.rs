@@ -0,0 +1,466 @@
+//! Decoding and tokenizing search terms, and re-encoding them for engine URLs.
+
+use std::fmt;
+
+use crate::engines::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTerms;
+
+impl fmt::Display for InvalidTerms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "search terms aren't valid UTF-8")
+    }
+}
+
+/// Decode form-encoded terms, where `+` is a space and `%2B` a literal `+`.
+///
+/// `+` has to become a space *before* percent-decoding, otherwise an encoded plus is lost.
+pub fn decode(encoded: &str) -> Result<String, InvalidTerms> {
+    let spaced = encoded.replace('+', " ");
+
+    String::from_utf8(urlencoding::decode_binary(spaced.as_bytes()).into_owned())
+        .map_err(|_| InvalidTerms)
+}
+
+/// Percent-encode decoded terms for substitution into an engine's `{s}`.
+pub fn encode(terms: &str) -> String {
+    urlencoding::encode(terms).into_owned()
+}
+
+/// Encode decoded terms for `position` in an engine's URL.
+pub fn encode_at(terms: &str, position: Position) -> String {
+    match position {
+        // percent-encoding isn't valid in a host, but international names can be punycode
+        Position::Host => terms
+            .split('.')
+            .map(|label| {
+                // punycode keeps ASCII as is, so it can't be used for anything but a clean label
+                let clean = label
+                    .chars()
+                    .all(|c| !c.is_ascii() || c.is_ascii_alphanumeric() || c == '-');
+                match punycode(&label.to_lowercase()).filter(|_| clean) {
+                    Some(label) => format!("xn--{label}"),
+                    None => encode(label),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("."),
+        // `.` and `..` would be resolved as relative path segments
+        Position::Path if matches!(terms, "." | "..") => terms.replace('.', "%2E"),
+        Position::Path | Position::Query => encode(terms),
+    }
+}
+
+/// Encode a non-ASCII domain name label as in RFC 3492, without the `xn--` prefix.
+fn punycode(label: &str) -> Option<String> {
+    const BASE: u32 = 36;
+    const T_MIN: u32 = 1;
+    const T_MAX: u32 = 26;
+
+    fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+        let mut delta = if first { delta / 700 } else { delta / 2 };
+        delta += delta / points;
+
+        let mut k = 0;
+        while delta > (BASE - T_MIN) * T_MAX / 2 {
+            delta /= BASE - T_MIN;
+            k += BASE;
+        }
+        k + (BASE - T_MIN + 1) * delta / (delta + 38)
+    }
+
+    fn digit(d: u32) -> char {
+        // `a` to `z`, then `0` to `9`, `d` is always below `BASE`
+        (if d < 26 {
+            b'a' + d as u8
+        } else {
+            b'0' + (d - 26) as u8
+        }) as char
+    }
+
+    if label.is_ascii() {
+        return None;
+    }
+
+    let points: Vec<u32> = label.chars().map(u32::from).collect();
+    let mut output: String = label.chars().filter(char::is_ascii).collect();
+    let basic = output.len() as u32;
+    if basic > 0 {
+        output.push('-');
+    }
+
+    let (mut n, mut delta, mut bias, mut handled) = (128, 0u32, 72, basic);
+
+    while (handled as usize) < points.len() {
+        let next = points.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((next - n).checked_mul(handled + 1)?)?;
+        n = next;
+
+        for &c in &points {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c != n {
+                continue;
+            }
+
+            let mut q = delta;
+            let mut k = BASE;
+            loop {
+                let t = k.saturating_sub(bias).clamp(T_MIN, T_MAX);
+                if q < t {
+                    break;
+                }
+                output.push(digit(t + (q - t) % (BASE - t)));
+                q = (q - t) / (BASE - t);
+                k += BASE;
+            }
+            output.push(digit(q));
+
+            bias = adapt(delta, handled + 1, handled == basic);
+            delta = 0;
+            handled += 1;
+        }
+
+        delta += 1;
+        n += 1;
+    }
+
+    Some(output)
+}
+
+/// A whitespace separated word of a query, quoted phrases count as a single word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    /// Byte offset of the token in the query.
+    pub start: usize,
+}
+
+impl Token<'_> {
+    /// Whether the token is a shortcut like `!w`, quoted phrases never are.
+    pub fn is_bang(&self) -> bool {
+        self.text.starts_with('!')
+    }
+}
+
+pub fn tokenize(terms: &str) -> Vec<Token<'_>> {
+    match split(terms, None) {
+        // an unclosed quote is just text, like in `27" monitor`
+        (_, Some(unclosed)) => split(terms, Some(unclosed)).0,
+        (tokens, None) => tokens,
+    }
+}
+
+/// Split `terms` into tokens, treating the quote at byte `literal` as text, along with where
+/// a quote that was never closed starts.
+fn split(terms: &str, literal: Option<usize>) -> (Vec<Token<'_>>, Option<usize>) {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quoted = None;
+
+    for (idx, c) in terms.char_indices() {
+        if c.is_whitespace() && quoted.is_none() {
+            if let Some(start) = start.take() {
+                tokens.push(Token {
+                    text: &terms[start..idx],
+                    start,
+                });
+            }
+            continue;
+        }
+
+        if c == '"' && literal != Some(idx) {
+            quoted = match quoted {
+                Some(_) => None,
+                None => Some(idx),
+            };
+        }
+
+        start.get_or_insert(idx);
+    }
+
+    if let Some(start) = start {
+        tokens.push(Token {
+            text: &terms[start..],
+            start,
+        });
+    }
+
+    (tokens, quoted)
+}
+
+/// Remove `token` and the whitespace after it (or before it, at the end of the query),
+/// leaving the rest of the query's spacing and quoting untouched.
+pub fn remove_token(terms: &str, token: &Token) -> String {
+    let mut start = token.start;
+    let mut end = token.start + token.text.len();
+
+    let rest = &terms[end..];
+    let following = rest.len() - rest.trim_start().len();
+
+    if following != rest.len() {
+        end += following;
+    } else {
+        let before = &terms[..start];
+        start -= before.len() - before.trim_end().len();
+    }
+
+    format!("{}{}", &terms[..start], &terms[end..])
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    /// Remove the first bang in `terms`.
+    fn remove_bang(terms: &str) -> String {
+        let token = tokenize(terms).into_iter().find(Token::is_bang).unwrap();
+        remove_token(terms, &token)
+    }
+
+    /// Encode like a browser submitting a form: spaces become `+`.
+    fn form_encode(terms: &str) -> String {
+        encode(terms).replace("%20", "+")
+    }
+
+    #[test]
+    fn plus_is_space() {
+        assert_eq!(decode("hello+world").unwrap(), "hello world");
+    }
+
+    #[test]
+    fn encoded_plus_is_plus() {
+        assert_eq!(decode("c%2B%2B").unwrap(), "c++");
+        assert_eq!(encode("c++"), "c%2B%2B");
+    }
+
+    #[test]
+    fn unicode() {
+        assert_eq!(decode("%E2%9C%93+caf%C3%A9").unwrap(), "✓ café");
+        assert_eq!(encode("✓ café"), "%E2%9C%93%20caf%C3%A9");
+    }
+
+    #[test]
+    fn encode_by_position() {
+        assert_eq!(encode_at("a b/c", Position::Query), "a%20b%2Fc");
+        assert_eq!(encode_at("a b/c", Position::Path), "a%20b%2Fc");
+        assert_eq!(encode_at("..", Position::Path), "%2E%2E");
+        assert_eq!(encode_at("..", Position::Query), "..");
+        assert_eq!(encode_at("日本", Position::Host), "xn--wgv71a");
+        assert_eq!(encode_at("München.de", Position::Host), "xn--mnchen-3ya.de");
+        assert_eq!(encode_at("en/x@y", Position::Host), "en%2Fx%40y");
+        assert_eq!(
+            encode_at("日本/x", Position::Host),
+            "%E6%97%A5%E6%9C%AC%2Fx"
+        );
+    }
+
+    #[test]
+    fn punycode_rfc3492_samples() {
+        // RFC 3492 section 7.1, whose outputs keep the case of ASCII letters like this does
+        let samples = [
+            ("ليهمابتكلموشعربي؟", "egbpdaj6bu4bxfgehfvwxn"),
+            ("他们为什么不说中文", "ihqwcrb4cv8a8dqg056pqjye"),
+            ("他們爲什麽不說中文", "ihqwctvzc91f659drss3x8bo0yb"),
+            ("Pročprostěnemluvíčesky", "Proprostnemluvesky-uyb24dma41a"),
+            ("למההםפשוטלאמדבריםעברית", "4dbcagdahymbxekheh6e0a7fei0b"),
+            (
+                "यहलोगहिन्दीक्योंनहींबोलसकतेहैं",
+                "i1baa7eci9glrd9b2ae1bj0hfcgg6iyaf8o0a1dig0cd",
+            ),
+            (
+                "なぜみんな日本語を話してくれないのか",
+                "n8jok5ay5dzabd5bym9f0cm5685rrjetr6pdxa",
+            ),
+            // the RFC marks one letter uppercase, which is only a hint for decoders
+            (
+                "почемужеонинеговорятпорусски",
+                "b1abfaaepdrnnbgefbadotcwatmq2g4l",
+            ),
+            (
+                "PorquénopuedensimplementehablarenEspañol",
+                "PorqunopuedensimplementehablarenEspaol-fmd56a",
+            ),
+            ("3年B組金八先生", "3B-ww4c5e180e575a65lsy2b"),
+            (
+                "安室奈美恵-with-SUPER-MONKEYS",
+                "-with-SUPER-MONKEYS-pc58ag80a8qai00g7n9n",
+            ),
+            (
+                "Hello-Another-Way-それぞれの場所",
+                "Hello-Another-Way--fc4qua05auwb3674vfr0b",
+            ),
+            ("ひとつ屋根の下2", "2-u9tlzr9756bt3uc0v"),
+            ("MajiでKoiする5秒前", "MajiKoi5-783gue6qz075azm5e"),
+            ("パフィーdeルンバ", "de-jg4avhby1noc0d"),
+            ("そのスピードで", "d9juau41awczczp"),
+        ];
+
+        for (label, encoded) in samples {
+            assert_eq!(punycode(label).as_deref(), Some(encoded), "{label}");
+        }
+    }
+
+    #[test]
+    fn punycode_edge_cases() {
+        // ASCII labels, including the RFC's "-> $1.00 <-", and empty ones are left to the caller
+        assert_eq!(punycode("-> $1.00 <-"), None);
+        assert_eq!(punycode("example"), None);
+        assert_eq!(punycode(""), None);
+
+        // hosts are case insensitive, so the label is lowercased before it's encoded
+        assert_eq!(encode_at("MÜNCHEN", Position::Host), "xn--mnchen-3ya");
+        assert_eq!(encode_at("MüNchen", Position::Host), "xn--mnchen-3ya");
+        assert_eq!(encode_at("Example.COM", Position::Host), "Example.COM");
+        assert_eq!(encode_at("a..ü.", Position::Host), "a..xn--tda.");
+        assert_eq!(encode_at("", Position::Host), "");
+        assert_eq!(encode_at("-ü-", Position::Host), "xn-----xka");
+    }
+
+    #[test]
+    fn invalid_utf8_is_an_error() {
+        assert_eq!(decode("%FF"), Err(InvalidTerms));
+        assert_eq!(decode("abc%C3"), Err(InvalidTerms));
+    }
+
+    #[test]
+    fn malformed_escapes_are_kept() {
+        assert_eq!(decode("100%").unwrap(), "100%");
+        assert_eq!(decode("%zz").unwrap(), "%zz");
+    }
+
+    #[test]
+    fn bang_at_either_end() {
+        assert_eq!(remove_bang("!w hello world"), "hello world");
+        assert_eq!(remove_bang("hello world !w"), "hello world");
+        assert_eq!(remove_bang("  !w  hello  "), "hello");
+    }
+
+    #[test]
+    fn spacing_is_preserved() {
+        assert_eq!(remove_bang("a  !w   b"), "a  b");
+        assert_eq!(remove_bang("a\t!w b  c"), "a\tb  c");
+    }
+
+    #[test]
+    fn only_the_matched_token_is_removed() {
+        assert_eq!(remove_bang("!r rust !rust"), "rust !rust");
+        assert_eq!(remove_bang("x!r !r y!r"), "x!r y!r");
+    }
+
+    #[test]
+    fn quoted_phrases_are_single_tokens() {
+        let tokens = tokenize(r#"say "hello !w world" !g"#);
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text).collect();
+        assert_eq!(texts, ["say", r#""hello !w world""#, "!g"]);
+
+        assert_eq!(
+            remove_bang(r#""hello !w world"  !g"#),
+            r#""hello !w world""#
+        );
+    }
+
+    #[test]
+    fn unclosed_quote_is_text() {
+        let tokens = tokenize(r#"27" monitor !a"#);
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text).collect();
+        assert_eq!(texts, [r#"27""#, "monitor", "!a"]);
+        assert_eq!(remove_bang(r#"27" monitor !a"#), r#"27" monitor"#);
+
+        let tokens = tokenize(r#""a b" 3" !w"#);
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text).collect();
+        assert_eq!(texts, [r#""a b""#, r#"3""#, "!w"]);
+    }
+
+    #[test]
+    fn lone_bang() {
+        assert_eq!(remove_bang("! secret"), "secret");
+        assert_eq!(remove_bang("!"), "");
+    }
+
+    proptest! {
+        #[test]
+        fn removing_a_bang_keeps_everything_else(
+            words in proptest::collection::vec("[a-z0-9!?.+-]{1,8}", 1..6),
+            spaces in proptest::collection::vec(" {1,3}", 6),
+            bang in "![a-z]{1,4}",
+            position in 0usize..6,
+        ) {
+            // words can't be bangs themselves, so the inserted one is the first
+            let words: Vec<String> = words.into_iter().map(|w| format!("x{w}")).collect();
+            let position = position.min(words.len());
+
+            let mut original = String::new();
+            let mut with_bang = String::new();
+
+            for (idx, word) in words.iter().enumerate() {
+                if idx == position {
+                    with_bang.push_str(&bang);
+                    with_bang.push_str(&spaces[idx]);
+                }
+                original.push_str(word);
+                with_bang.push_str(word);
+                if idx + 1 != words.len() {
+                    original.push_str(&spaces[idx]);
+                    with_bang.push_str(&spaces[idx]);
+                }
+            }
+            if position == words.len() {
+                with_bang.push_str(&spaces[position]);
+                with_bang.push_str(&bang);
+            }
+
+            prop_assert_eq!(remove_bang(&with_bang), original);
+        }
+
+        #[test]
+        fn tokens_point_into_the_query(terms in any::<String>()) {
+            for token in tokenize(&terms) {
+                prop_assert_eq!(&terms[token.start..token.start + token.text.len()], token.text);
+                prop_assert!(!token.text.is_empty());
+            }
+        }
+
+        #[test]
+        fn form_round_trip(terms in any::<String>()) {
+            prop_assert_eq!(decode(&form_encode(&terms)).unwrap(), terms);
+        }
+
+        #[test]
+        fn percent_round_trip(terms in any::<String>()) {
+            prop_assert_eq!(decode(&encode(&terms)).unwrap(), terms);
+        }
+
+        #[test]
+        fn encoded_is_url_safe(terms in any::<String>()) {
+            let encoded = encode(&terms);
+            prop_assert!(encoded
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-._~%".contains(&b)));
+        }
+
+        #[test]
+        fn arbitrary_bytes_never_panic(bytes in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let encoded: String = bytes.iter().map(|b| format!("%{b:02X}")).collect();
+            prop_assert_eq!(decode(&encoded).is_ok(), String::from_utf8(bytes).is_ok());
+        }
+
+        #[test]
+        fn arbitrary_input_never_panics(input in any::<String>()) {
+            let _ = decode(&input);
+        }
+
+        #[test]
+        fn encoded_terms_are_ascii(input in any::<String>()) {
+            for position in [Position::Host, Position::Path, Position::Query] {
+                prop_assert!(encode_at(&input, position).is_ascii());
+            }
+        }
+    }
+}
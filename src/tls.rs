@@ -0,0 +1,157 @@
+//! HTTPS listener support and the `generate-certificate` subcommand.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use tiny_http::SslConfig;
+
+use crate::config::TlsConfig;
+
+pub const CERTIFICATE_FILE: &str = "localhost.pem";
+pub const KEY_FILE: &str = "localhost-key.pem";
+
+pub fn ssl_config(tls: &TlsConfig) -> Result<SslConfig, String> {
+    let (Some(certificate), Some(key)) = (&tls.certificate, &tls.key) else {
+        return Err("both tls.certificate and tls.key must be set".into());
+    };
+
+    let private_key = fs::read(key).map_err(|e| format!("failed to read key {key:?}: {e}"))?;
+    check_key(&private_key).map_err(|e| format!("can't use key {key:?}: {e}"))?;
+
+    Ok(SslConfig {
+        certificate: fs::read(certificate)
+            .map_err(|e| format!("failed to read certificate {certificate:?}: {e}"))?,
+        private_key,
+    })
+}
+
+/// tiny_http panics on keys other than PKCS#8 or RSA ones, like SEC1 EC keys, so look for one
+/// the same way it does first.
+fn check_key(pem: &[u8]) -> Result<(), String> {
+    let pkcs8 = rustls_pemfile::pkcs8_private_keys(&mut &pem[..])
+        .map_err(|e| format!("invalid PEM: {e}"))?;
+    let rsa =
+        rustls_pemfile::rsa_private_keys(&mut &pem[..]).map_err(|e| format!("invalid PEM: {e}"))?;
+
+    if pkcs8.is_empty() && rsa.is_empty() {
+        return Err("no unencrypted PKCS#8 or RSA private key found, \
+            convert it with `openssl pkcs8 -topk8 -nocrypt`"
+            .into());
+    }
+
+    Ok(())
+}
+
+/// Where `generate-certificate` writes to when no directory is given.
+pub fn default_directory() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join("local-search-shortcuts"))
+        .unwrap_or_default()
+}
+
+/// Write a self-signed certificate for `localhost` and its key into `dir`, replacing existing
+/// files only if `force` is set.
+pub fn generate_certificate(dir: &Path, force: bool) -> Result<(PathBuf, PathBuf), String> {
+    let cert = rcgen::generate_simple_self_signed(vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ])
+    .map_err(|e| e.to_string())?;
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let cert_path = dir.join(CERTIFICATE_FILE);
+    let key_path = dir.join(KEY_FILE);
+
+    for path in [&cert_path, &key_path] {
+        if !path.exists() {
+            continue;
+        }
+        if !force {
+            return Err(format!(
+                "{path:?} already exists, use --force to replace it"
+            ));
+        }
+        // removed rather than truncated, so an old key's permissions don't carry over
+        fs::remove_file(path).map_err(|e| format!("failed to remove {path:?}: {e}"))?;
+    }
+
+    write_new(
+        &cert_path,
+        &cert.serialize_pem().map_err(|e| e.to_string())?,
+        0o644,
+    )?;
+    write_new(&key_path, &cert.serialize_private_key_pem(), 0o600)?;
+
+    Ok((cert_path, key_path))
+}
+
+/// Create `path` with `mode` on Unix, failing if it exists.
+fn write_new(path: &Path, contents: &str, mode: u32) -> Result<(), String> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| format!("failed to write {path:?}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let pkcs8 = cert.serialize_private_key_pem();
+
+        assert_eq!(check_key(pkcs8.as_bytes()), Ok(()));
+        assert!(check_key(b"").is_err());
+        assert!(check_key(pkcs8.replace("PRIVATE KEY", "EC PRIVATE KEY").as_bytes()).is_err());
+        assert!(check_key(
+            pkcs8
+                .replace("PRIVATE KEY", "ENCRYPTED PRIVATE KEY")
+                .as_bytes()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn overwrite() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("tls");
+
+        let (cert, key) = generate_certificate(&dir, false).unwrap();
+        let original = fs::read(&key).unwrap();
+        assert!(check_key(&original).is_ok());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&key).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        assert!(generate_certificate(&dir, false).is_err());
+        assert_eq!(fs::read(&key).unwrap(), original);
+
+        fs::remove_file(&key).unwrap();
+        assert!(
+            generate_certificate(&dir, false).is_err(),
+            "{cert:?} is still there"
+        );
+
+        generate_certificate(&dir, true).unwrap();
+        assert_ne!(fs::read(&key).unwrap(), original);
+    }
+}